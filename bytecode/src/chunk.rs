@@ -4,6 +4,8 @@ use crate::value::{Value, ValueArray};
 use std::mem;
 use std::ptr;
 
+// The `Op` prefix mirrors the `OP_` names used in the book.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum OpCode {
     OpConstant(usize),
@@ -60,11 +62,7 @@ impl Chunk {
         // Write chunk to code array.
         unsafe {
             let value: *const OpCode = &byte;
-            ptr::copy_nonoverlapping(
-                value,
-                self.code.offset(self.count as isize),
-                mem::size_of::<OpCode>(),
-            );
+            ptr::copy_nonoverlapping(value, self.code.add(self.count), 1);
         }
         self.count += 1;
 
@@ -95,7 +93,7 @@ impl Chunk {
     }
 
     fn free_array(&mut self) {
-        reallocate(self.code, mem::size_of::<u8>() * self.capacity, 0);
+        reallocate(self.code, mem::size_of::<OpCode>() * self.capacity, 0);
     }
 }
 
@@ -137,7 +135,7 @@ impl<'a> Iterator for ChunkIter<'a> {
                 result = self
                     .chunk
                     .code
-                    .offset(self.offset as isize)
+                    .add(self.offset)
                     .as_ref()
                     .expect("Could not read Chunk.");
             }
@@ -3,6 +3,7 @@ use crate::debug::disassemble_chunk;
 use crate::scanner::{Scanner, Token, TokenType};
 use crate::value::Value;
 
+use diagnostics::{Diagnostic, Emitter};
 use std::collections::HashMap;
 use std::convert::From;
 use std::mem;
@@ -94,17 +95,22 @@ pub struct Compiler<'a> {
     compiling_chunk: Chunk,
     scanner: Scanner<'a>,
     parse_rules: HashMap<TokenType, ParseRule<'a>>,
+    source: &'a str,
+    emitter: Emitter,
 }
 
 impl<'a> Compiler<'a> {
     #[rustfmt::skip::macros(parse_rule)]
-    pub fn new() -> Self {
+    pub fn new(emitter: Emitter) -> Self {
         let parse_rules: HashMap<TokenType, ParseRule> = {
             let mut m: HashMap<TokenType, ParseRule> = HashMap::new();
             parse_rule!(m, LeftParen    => Some(Compiler::grouping), None,                   None);
             parse_rule!(m, RightParen   => None,                     None,                   None);
             parse_rule!(m, LeftBrace    => None,                     None,                   None);
             parse_rule!(m, RightBrace   => None,                     None,                   None);
+            parse_rule!(m, LeftBracket  => None,                     None,                   None);
+            parse_rule!(m, RightBracket => None,                     None,                   None);
+            parse_rule!(m, Colon        => None,                     None,                   None);
             parse_rule!(m, Comma        => None,                     None,                   None);
             parse_rule!(m, Dot          => None,                     None,                   None);
             parse_rule!(m, Minus        => Some(Compiler::unary),    Some(Compiler::binary), Term);
@@ -124,19 +130,26 @@ impl<'a> Compiler<'a> {
             parse_rule!(m, String       => None,                     None,                   None);
             parse_rule!(m, Number       => Some(Compiler::number),   None,                   None);
             parse_rule!(m, And          => None,                     None,                   None);
+            parse_rule!(m, Break        => None,                     None,                   None);
+            parse_rule!(m, Catch        => None,                     None,                   None);
             parse_rule!(m, Class        => None,                     None,                   None);
+            parse_rule!(m, Continue     => None,                     None,                   None);
             parse_rule!(m, Else         => None,                     None,                   None);
             parse_rule!(m, False        => Some(Compiler::literal),  None,                   None);
+            parse_rule!(m, Finally      => None,                     None,                   None);
             parse_rule!(m, For          => None,                     None,                   None);
             parse_rule!(m, Fun          => None,                     None,                   None);
             parse_rule!(m, If           => None,                     None,                   None);
+            parse_rule!(m, Import       => None,                     None,                   None);
             parse_rule!(m, Nil          => Some(Compiler::literal),  None,                   None);
             parse_rule!(m, Or           => None,                     None,                   None);
             parse_rule!(m, Print        => None,                     None,                   None);
             parse_rule!(m, Return       => None,                     None,                   None);
             parse_rule!(m, Super        => None,                     None,                   None);
             parse_rule!(m, This         => None,                     None,                   None);
+            parse_rule!(m, Throw        => None,                     None,                   None);
             parse_rule!(m, True         => Some(Compiler::literal),  None,                   None);
+            parse_rule!(m, Try          => None,                     None,                   None);
             parse_rule!(m, Var          => None,                     None,                   None);
             parse_rule!(m, While        => None,                     None,                   None);
             parse_rule!(m, Error        => None,                     None,                   None);
@@ -148,12 +161,15 @@ impl<'a> Compiler<'a> {
             parser: Parser::default(),
             compiling_chunk: Chunk::new(),
             scanner: Scanner::new(""),
-            parse_rules: parse_rules,
+            parse_rules,
+            source: "",
+            emitter,
         }
     }
 
     pub fn compile(&mut self, source: &'a str) -> Option<Chunk> {
         self.scanner = Scanner::new(source);
+        self.source = source;
 
         self.parser.had_error = false;
         self.parser.panic_mode = false;
@@ -180,15 +196,10 @@ impl<'a> Compiler<'a> {
         }
         self.parser.panic_mode = true;
 
-        eprint!("[line {}] Error", token.line);
-
-        match token.typ {
-            TokenType::EOF => eprint!(" at end"),
-            TokenType::Error => (),
-            _ => eprint!(" at '{}'", token.src),
-        }
-
-        eprintln!(": {}", message);
+        // Sources end with a nul so the scanner can peek past the end.
+        let source = self.source.trim_end_matches('\0');
+        let diagnostic = Diagnostic::error(message).with_primary(token.span, "");
+        self.emitter.emit(&diagnostic, source);
         self.parser.had_error = true;
     }
 
@@ -251,10 +262,8 @@ impl<'a> Compiler<'a> {
     fn end_compiler(&mut self) {
         self.emit_return();
 
-        if cfg!(feature = "debug_trace_execution") {
-            if self.parser.had_error {
-                disassemble_chunk(self.current_chunk(), "code");
-            }
+        if cfg!(feature = "debug_trace_execution") && self.parser.had_error {
+            disassemble_chunk(self.current_chunk(), "code");
         }
     }
 
@@ -45,6 +45,5 @@ pub fn disassemble_instruction(chunk: &Chunk, op_code: &OpCode, i: usize) {
         OpCode::OpNot => simple_instruction("OP_NOT"),
         OpCode::OpNegate => simple_instruction("OP_NEGATE"),
         OpCode::OpReturn => simple_instruction("OP_RETURN"),
-        _ => println!("Unknown opcode {:?}", op_code),
     }
 }
@@ -5,12 +5,20 @@ use std::io;
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+
+    /// The compiler reported errors.
+    Compile,
+
+    /// The VM reported an error.
+    Runtime,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::Io(underlying) => write!(f, "IoError {}", underlying),
+            Error::Compile => write!(f, "CompileError"),
+            Error::Runtime => write!(f, "RuntimeError"),
         }
     }
 }
@@ -8,26 +8,218 @@ mod value;
 mod vm;
 
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::process::exit;
 
+use compiler::Compiler;
+use diagnostics::{Diagnostic, Emitter, Format};
 use error::Error;
+use memory::HeapSnapshot;
+use scanner::{Scanner, TokenType};
 use vm::{InterpretResult, VM};
 
+const USAGE: &str = "\
+Usage: lox-rs [options] [command] [script | -] [arguments...]
+
+Commands:
+  run       Run the script. This is the default when a script is given.
+  repl      Start the REPL. This is the default without a script.
+  check     Compile the script without running it.
+  tokens    Print the tokens of the script.
+  heap-diff <before> <after>
+            Show what was allocated and freed between two heap snapshots.
+
+Options:
+  -e <code>                    Use the code instead of a script.
+  --error-format=human|json    How errors are printed.
+  --heap-snapshot=<file>       Write the live allocations to the file after running the script.
+                               In the REPL, ':snapshot <file>' writes them at any point.
+  -h, --help                   Show this help.
+
+The script is read from stdin if it is '-'. There are no functions yet, so the arguments after
+it are ignored.";
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    File(String),
+    Stdin,
+    Inline(String),
+}
+
+impl Input {
+    /// Returns the name errors are reported with and the source, terminated by a nul.
+    fn read(&self) -> Result<(String, String), Error> {
+        let (name, source) = match self {
+            Input::File(path) => match fs::read_to_string(path) {
+                Ok(source) => (path.clone(), source),
+                Err(e) => {
+                    return Err(Error::Io(io::Error::new(
+                        e.kind(),
+                        format!("Could not read '{}': {}.", path, e),
+                    )))
+                }
+            },
+            Input::Stdin => ("<stdin>".to_string(), io::read_to_string(io::stdin())?),
+            Input::Inline(code) => ("<-e>".to_string(), code.clone()),
+        };
+        Ok((name, format!("{}\0", source)))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Repl,
+    Run(Input),
+    Check(Input),
+    Tokens(Input),
+    HeapDiff(String, String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    format: Format,
+    heap_snapshot: Option<String>,
+    arguments: Vec<String>,
+}
+
+/// Parses the command line without the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut name: Option<&str> = None;
+    let mut input: Option<Input> = None;
+    let mut format = Format::Human;
+    let mut heap_snapshot: Option<String> = None;
+
+    let mut args = args.iter();
+    while input.is_none() {
+        let arg = match args.next() {
+            Some(arg) => arg.as_str(),
+            None => break,
+        };
+        match arg {
+            "-h" | "--help" => name = Some("help"),
+            "-e" => {
+                let code = args.next().ok_or("Missing code after '-e'.")?;
+                input = Some(Input::Inline(code.clone()));
+            }
+            "-" => input = Some(Input::Stdin),
+            "ast" if name.is_none() => {
+                return Err("The bytecode compiler does not build a syntax tree.".to_string())
+            }
+            "run" | "repl" | "check" | "tokens" | "heap-diff" if name.is_none() => name = Some(arg),
+            _ if arg.starts_with("--error-format=") => {
+                format = arg["--error-format=".len()..].parse()?
+            }
+            _ if arg.starts_with("--heap-snapshot=") => {
+                heap_snapshot = Some(arg["--heap-snapshot=".len()..].to_string())
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'.", arg)),
+            _ => input = Some(Input::File(arg.to_string())),
+        }
+    }
+
+    let default = if input.is_some() { "run" } else { "repl" };
+    let command = match (name.unwrap_or(default), input) {
+        ("help", _) => Command::Help,
+        ("repl", None) => Command::Repl,
+        ("repl", Some(_)) => return Err("The REPL does not take a script.".to_string()),
+        (_, None) => return Err("Missing script.".to_string()),
+        ("run", Some(input)) => Command::Run(input),
+        ("check", Some(input)) => Command::Check(input),
+        ("tokens", Some(input)) => Command::Tokens(input),
+        ("heap-diff", Some(Input::File(before))) => match args.next() {
+            Some(after) => Command::HeapDiff(before, after.clone()),
+            None => return Err("Missing snapshot to compare with.".to_string()),
+        },
+        ("heap-diff", Some(_)) => return Err("Heap snapshots are read from files.".to_string()),
+        _ => unreachable!(),
+    };
+
+    Ok(Options {
+        command,
+        format,
+        heap_snapshot,
+        arguments: args.cloned().collect(),
+    })
+}
+
 struct Lox {
     vm: VM,
+    format: Format,
+
+    /// Where `run` writes the heap snapshot.
+    heap_snapshot: Option<String>,
 }
 
 impl Lox {
-    fn new() -> Self {
-        Lox { vm: VM::new() }
+    fn new(format: Format, heap_snapshot: Option<String>) -> Self {
+        Lox {
+            vm: VM::new(),
+            format,
+            heap_snapshot,
+        }
     }
 
+    fn write_heap_snapshot(&self, path: &str) -> Result<(), Error> {
+        let mut file = fs::File::create(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Could not write '{}': {}.", path, e)))?;
+        self.vm.heap_snapshot().write(&mut file)?;
+        Ok(())
+    }
+
+    /// Runs a command that works on a script.
+    fn execute(&mut self, command: &Command) -> Result<(), Error> {
+        match command {
+            Command::Run(input) => {
+                let (file, source) = input.read()?;
+                self.vm.set_emitter(Emitter::new(&file, self.format));
+                let result = self.vm.interpret(&source);
+                if let Some(path) = &self.heap_snapshot {
+                    self.write_heap_snapshot(path)?;
+                }
+                match result {
+                    InterpretResult::CompileError => Err(Error::Compile),
+                    InterpretResult::RuntimeError => Err(Error::Runtime),
+                    InterpretResult::Ok => Ok(()),
+                }
+            }
+            Command::Check(input) => {
+                let (file, source) = input.read()?;
+                let mut compiler = Compiler::new(Emitter::new(&file, self.format));
+                compiler.compile(&source).map(|_| ()).ok_or(Error::Compile)
+            }
+            Command::Tokens(input) => {
+                let (_, source) = input.read()?;
+                print_tokens(&source)
+            }
+            Command::HeapDiff(before, after) => {
+                let read = |path: &str| {
+                    fs::File::open(path)
+                        .and_then(|file| HeapSnapshot::read(io::BufReader::new(file)))
+                        .map_err(|e| {
+                            io::Error::new(e.kind(), format!("Could not read '{}': {}.", path, e))
+                        })
+                };
+                print!("{}", read(before)?.diff(&read(after)?));
+                Ok(())
+            }
+            Command::Help | Command::Repl => unreachable!(),
+        }
+    }
+
+    /// Reads lines until the input is complete and interprets them.
+    ///
+    /// The value of each entry is printed since every chunk ends with `OpReturn`. `:snapshot <file>`
+    /// writes a heap snapshot instead.
     fn repl(&mut self) -> Result<(), Error> {
+        self.vm.set_emitter(Emitter::new("<stdin>", self.format));
         let stdin = io::stdin();
+        let mut source = String::new();
 
         loop {
-            print!("> ");
+            print!("{}", if source.is_empty() { "> " } else { ". " });
+            io::stdout().flush()?;
 
             let mut line = String::new();
             if stdin.read_line(&mut line)? == 0 {
@@ -35,38 +227,143 @@ impl Lox {
                 break;
             }
 
-            self.vm.interpret(&line);
+            if source.is_empty() {
+                if let Some(path) = line.trim().strip_prefix(":snapshot ") {
+                    if let Err(Error::Io(e)) = self.write_heap_snapshot(path.trim()) {
+                        let diagnostic = Diagnostic::error(&e.to_string());
+                        Emitter::new("", self.format).emit(&diagnostic, "");
+                    }
+                    continue;
+                }
+            }
+
+            source.push_str(&line);
+            if !is_complete(&source) {
+                continue;
+            }
+
+            self.vm.interpret(&source);
+            source.clear();
+        }
+
+        // Report errors in an unfinished entry.
+        if !source.is_empty() {
+            self.vm.interpret(&source);
         }
 
         Ok(())
     }
+}
 
-    fn run_file(&mut self, path: &str) -> Result<(), Error> {
-        let source = format!("{}\0", fs::read_to_string(path)?);
+/// Prints the tokens like clox does while it scans on demand.
+fn print_tokens(source: &str) -> Result<(), Error> {
+    let mut scanner = Scanner::new(source);
+    let mut line = -1;
+    let mut had_error = false;
+    loop {
+        let token = scanner.scan_token();
+        if token.line != line {
+            print!("{:4} ", token.line);
+            line = token.line;
+        } else {
+            print!("   | ");
+        }
+        println!("{:2} '{}'", token.typ as i32, token.src);
+
+        match token.typ {
+            TokenType::Error => had_error = true,
+            TokenType::EOF if had_error => return Err(Error::Compile),
+            TokenType::EOF => return Ok(()),
+            _ => (),
+        }
+    }
+}
 
-        match self.vm.interpret(&source) {
-            InterpretResult::CompileError => exit(65),
-            InterpretResult::RuntimeError => exit(70),
-            InterpretResult::Ok => Ok(()),
+/// Returns false if the source has unbalanced parentheses or braces, or an unterminated string.
+fn is_complete(source: &str) -> bool {
+    let mut scanner = Scanner::new(source);
+    let mut depth = 0;
+    loop {
+        let token = scanner.scan_token();
+        match token.typ {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            TokenType::Error if token.src == "Unterminated string." => return false,
+            TokenType::EOF => return depth <= 0,
+            _ => (),
         }
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
-    //let mut chunk = Chunk::new();
+/// The exit code of an error, following sysexits.h.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Compile => 65, // EX_DATAERR
+        Error::Io(_) => 74,   // EX_IOERR
+        Error::Runtime => 70, // EX_SOFTWARE
+    }
+}
 
-    let mut program = Lox::new();
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        exit(64) // EX_USAGE
+    });
 
-    let args: Vec<String> = std::env::args().collect();
-    match args.as_slice() {
-        [_, file] => program.run_file(file)?,
-        [_] => program.repl()?,
-        _ => {
-            eprintln!("Usage: lox-rs [script]");
-            exit(64)
+    let mut program = Lox::new(options.format, options.heap_snapshot.clone());
+    let result = match &options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
         }
-    }
+        Command::Repl => program.repl(),
+        command => program.execute(command),
+    };
 
     // No need to free chunk since we implemented `Drop`.
-    Ok(())
+    if let Err(e) = result {
+        if let Error::Io(underlying) = &e {
+            let diagnostic = Diagnostic::error(&underlying.to_string());
+            Emitter::new("", options.format).emit(&diagnostic, "");
+        }
+        exit(exit_code(&e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line() {
+        let parse = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            parse_args(&args)
+        };
+
+        assert_eq!(parse(&[]).unwrap().command, Command::Repl);
+        assert_eq!(
+            parse(&["tokens", "-e", "1 + 2"]).unwrap().command,
+            Command::Tokens(Input::Inline("1 + 2".to_string()))
+        );
+        assert!(parse(&["ast", "a.lox"]).is_err());
+
+        let options = parse(&["--heap-snapshot=heap.txt", "a.lox"]).unwrap();
+        assert_eq!(options.heap_snapshot, Some("heap.txt".to_string()));
+        assert_eq!(
+            parse(&["heap-diff", "a.txt", "b.txt"]).unwrap().command,
+            Command::HeapDiff("a.txt".to_string(), "b.txt".to_string())
+        );
+        assert!(parse(&["heap-diff", "a.txt"]).is_err());
+    }
+
+    #[test]
+    fn complete_entries() {
+        assert!(is_complete("1 + 2\n"));
+        assert!(is_complete("(1 + 2))\n"));
+        assert!(!is_complete("(1 +\n"));
+        assert!(!is_complete("((1 + 2) // )\n"));
+        assert!(!is_complete("{\n"));
+    }
 }
@@ -1,7 +1,298 @@
 use std::alloc;
+use std::any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, BufRead, Write};
 use std::mem;
 use std::ptr;
 
+/// Live allocations and their size for one kind of allocation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KindStats {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Memory statistics of all allocations made through `reallocate`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeapStats {
+    pub bytes_allocated: usize,
+
+    /// Statistics per allocated type, keyed by the type name.
+    pub kinds: BTreeMap<&'static str, KindStats>,
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<32} {:>8} {:>10}", "kind", "count", "bytes")?;
+        for (kind, stats) in &self.kinds {
+            writeln!(f, "{:<32} {:>8} {:>10}", kind, stats.count, stats.bytes)?;
+        }
+        write!(f, "{:<32} {:>8} {:>10}", "total", "", self.bytes_allocated)
+    }
+}
+
+/// A live allocation as it appears in a `HeapSnapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapObject {
+    /// Stays the same when the allocation is resized, unlike its address.
+    pub id: usize,
+    pub kind: String,
+    pub bytes: usize,
+
+    /// The ids of the objects this one points to.
+    pub references: Vec<usize>,
+}
+
+/// Every live allocation and the roots they are reachable from at one point in time.
+///
+/// Snapshots are written to and read from a tab-separated text file so that two of them can be
+/// compared with `diff`, e.g. by the `heap-diff` command.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeapSnapshot {
+    pub roots: Vec<usize>,
+    pub objects: Vec<HeapObject>,
+}
+
+impl HeapSnapshot {
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        for root in &self.roots {
+            writeln!(out, "root\t{}", root)?;
+        }
+        for object in &self.objects {
+            let references: Vec<String> = object.references.iter().map(|r| r.to_string()).collect();
+            writeln!(
+                out,
+                "object\t{}\t{}\t{}\t{}",
+                object.id,
+                object.kind,
+                object.bytes,
+                references.join(",")
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn read(input: impl BufRead) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid heap snapshot line '{}'.", line),
+            )
+        };
+        let number = |field: Option<&str>, line: &str| {
+            field
+                .and_then(|field| field.parse::<usize>().ok())
+                .ok_or_else(|| invalid(line))
+        };
+
+        let mut snapshot = HeapSnapshot::default();
+        for line in input.lines() {
+            let line = line?;
+            let mut fields = line.split('\t');
+            match fields.next() {
+                Some("root") => snapshot.roots.push(number(fields.next(), &line)?),
+                Some("object") => {
+                    let id = number(fields.next(), &line)?;
+                    let kind = fields.next().ok_or_else(|| invalid(&line))?.to_string();
+                    let bytes = number(fields.next(), &line)?;
+                    let references = match fields.next() {
+                        Some("") | None => Vec::new(),
+                        Some(references) => references
+                            .split(',')
+                            .map(|r| number(Some(r), &line))
+                            .collect::<io::Result<_>>()?,
+                    };
+                    snapshot.objects.push(HeapObject {
+                        id,
+                        kind,
+                        bytes,
+                        references,
+                    });
+                }
+                Some("") => (),
+                _ => return Err(invalid(&line)),
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Compares this snapshot with a later one.
+    pub fn diff(&self, later: &HeapSnapshot) -> HeapDiff {
+        let before: BTreeSet<usize> = self.objects.iter().map(|o| o.id).collect();
+        let after: BTreeSet<usize> = later.objects.iter().map(|o| o.id).collect();
+
+        let mut kinds: BTreeMap<String, KindDiff> = BTreeMap::new();
+        for object in &self.objects {
+            let kind = kinds.entry(object.kind.clone()).or_default();
+            kind.count -= 1;
+            kind.bytes -= object.bytes as isize;
+        }
+        for object in &later.objects {
+            let kind = kinds.entry(object.kind.clone()).or_default();
+            kind.count += 1;
+            kind.bytes += object.bytes as isize;
+        }
+        kinds.retain(|_, kind| *kind != KindDiff::default());
+
+        HeapDiff {
+            kinds,
+            added: later
+                .objects
+                .iter()
+                .filter(|o| !before.contains(&o.id))
+                .cloned()
+                .collect(),
+            freed: self
+                .objects
+                .iter()
+                .filter(|o| !after.contains(&o.id))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// The change of the live allocations of one kind between two snapshots.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KindDiff {
+    pub count: isize,
+    pub bytes: isize,
+}
+
+/// What changed between two heap snapshots.
+///
+/// Objects that were allocated after the first snapshot and are still alive in the second are the
+/// ones to look at when memory grows.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HeapDiff {
+    /// The kinds whose count or size changed, keyed by the type name.
+    pub kinds: BTreeMap<String, KindDiff>,
+    pub added: Vec<HeapObject>,
+    pub freed: Vec<HeapObject>,
+}
+
+impl fmt::Display for HeapDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<32} {:>8} {:>10}", "kind", "count", "bytes")?;
+        for (kind, diff) in &self.kinds {
+            writeln!(f, "{:<32} {:>+8} {:>+10}", kind, diff.count, diff.bytes)?;
+        }
+        for (title, objects) in &[("added", &self.added), ("freed", &self.freed)] {
+            if !objects.is_empty() {
+                writeln!(f, "\n{}:", title)?;
+            }
+            for object in objects.iter() {
+                writeln!(
+                    f,
+                    "  #{} {} ({} bytes)",
+                    object.id, object.kind, object.bytes
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Allocation {
+    id: usize,
+    kind: &'static str,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct Heap {
+    stats: HeapStats,
+
+    /// Live allocations keyed by their address.
+    allocations: HashMap<usize, Allocation>,
+    next_id: usize,
+}
+
+thread_local! {
+    // The book keeps `bytesAllocated` in the VM. `reallocate` has no access to the VM so we keep
+    // the statistics per thread instead, which is the same as long as there is one VM per thread.
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+/// Returns the statistics of all allocations of the current thread that are still alive.
+pub fn heap_stats() -> HeapStats {
+    HEAP.with(|heap| heap.borrow().stats.clone())
+}
+
+/// Returns a snapshot of all allocations of the current thread that are still alive.
+///
+/// Roots are given by address. The only allocations so far are chunk code arrays, which don't
+/// point to other allocations, so no object has references yet.
+pub fn heap_snapshot<T>(roots: &[*const T]) -> HeapSnapshot {
+    HEAP.with(|heap| {
+        let heap = heap.borrow();
+        let mut objects: Vec<HeapObject> = heap
+            .allocations
+            .values()
+            .map(|allocation| HeapObject {
+                id: allocation.id,
+                kind: allocation.kind.to_string(),
+                bytes: allocation.bytes,
+                references: Vec::new(),
+            })
+            .collect();
+        objects.sort_by_key(|object| object.id);
+
+        HeapSnapshot {
+            roots: roots
+                .iter()
+                .filter_map(|root| heap.allocations.get(&(*root as usize)))
+                .map(|allocation| allocation.id)
+                .collect(),
+            objects,
+        }
+    })
+}
+
+fn track<T>(previous: *mut T, result: *mut T, old_size: usize, new_size: usize) {
+    HEAP.with(|heap| {
+        let heap = &mut *heap.borrow_mut();
+        let stats = &mut heap.stats;
+        stats.bytes_allocated = stats.bytes_allocated + new_size - old_size;
+
+        let kind = stats.kinds.entry(any::type_name::<T>()).or_default();
+        kind.bytes = kind.bytes + new_size - old_size;
+        if old_size == 0 {
+            kind.count += 1;
+        }
+        if new_size == 0 {
+            kind.count -= 1;
+        }
+        if kind.count == 0 {
+            stats.kinds.remove(any::type_name::<T>());
+        }
+
+        let allocation = match heap.allocations.remove(&(previous as usize)) {
+            Some(allocation) => allocation,
+            None => {
+                heap.next_id += 1;
+                Allocation {
+                    id: heap.next_id,
+                    kind: any::type_name::<T>(),
+                    bytes: 0,
+                }
+            }
+        };
+        if new_size != 0 {
+            heap.allocations.insert(
+                result as usize,
+                Allocation {
+                    bytes: new_size,
+                    ..allocation
+                },
+            );
+        }
+    });
+}
+
 /// Re-implements reallocation.
 ///
 /// This is for learning purposes and as the books states:
@@ -11,17 +302,89 @@ use std::ptr;
 /// > Routing all of those operations through a single function will be important later when we add
 /// > a garbage collector that needs to keep track of how much memory is in use.
 ///
+/// Every call is accounted for in the statistics returned by `heap_stats` and in the allocations
+/// listed by `heap_snapshot`.
 pub fn reallocate<T>(previous: *mut T, old_size: usize, new_size: usize) -> *mut T {
-    // TODO: make generic when required.
     // TODO: we might just want to use libc::free and libc::realloc.
-    unsafe {
+    if old_size == new_size {
+        return previous;
+    }
+
+    let result = unsafe {
         let layout = alloc::Layout::from_size_align(old_size, mem::align_of::<T>())
             .expect("Could not determine Layout for reallocation.");
         if new_size == 0 {
             alloc::dealloc(previous as *mut u8, layout);
             ptr::null_mut()
+        } else if old_size == 0 {
+            // Unlike C's realloc, Rust's does not accept a null pointer.
+            let layout = alloc::Layout::from_size_align(new_size, mem::align_of::<T>())
+                .expect("Could not determine Layout for allocation.");
+            alloc::alloc(layout) as *mut T
         } else {
             alloc::realloc(previous as *mut u8, layout, new_size) as *mut T
         }
+    };
+    track(previous, result, old_size, new_size);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_allocations_per_kind() {
+        let before = heap_stats();
+
+        let a: *mut u64 = reallocate(ptr::null_mut(), 0, 16);
+        let a = reallocate(a, 16, 64);
+        let b: *mut u32 = reallocate(ptr::null_mut(), 0, 8);
+
+        let stats = heap_stats();
+        assert_eq!(stats.bytes_allocated, before.bytes_allocated + 72);
+        assert_eq!(
+            stats.kinds["u64"],
+            KindStats {
+                count: 1,
+                bytes: 64
+            }
+        );
+        assert_eq!(stats.kinds["u32"], KindStats { count: 1, bytes: 8 });
+
+        reallocate(a, 64, 0);
+        reallocate(b, 8, 0);
+        assert_eq!(heap_stats(), before);
+    }
+
+    #[test]
+    fn snapshots_diff_what_was_allocated_in_between() {
+        let a: *mut u64 = reallocate(ptr::null_mut(), 0, 16);
+        let before = heap_snapshot(&[a]);
+
+        let a = reallocate(a, 16, 32);
+        let b: *mut u32 = reallocate(ptr::null_mut(), 0, 8);
+        let after = heap_snapshot(&[a]);
+        assert_eq!(after.roots, before.roots);
+
+        let mut file = Vec::new();
+        after.write(&mut file).unwrap();
+        assert_eq!(HeapSnapshot::read(&file[..]).unwrap(), after);
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.kinds["u64"],
+            KindDiff {
+                count: 0,
+                bytes: 16
+            }
+        );
+        assert_eq!(diff.kinds["u32"], KindDiff { count: 1, bytes: 8 });
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].kind, "u32");
+        assert!(diff.freed.is_empty());
+
+        reallocate(a, 32, 0);
+        reallocate(b, 8, 0);
     }
 }
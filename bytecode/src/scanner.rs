@@ -1,12 +1,18 @@
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use diagnostics::Span;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -32,23 +38,31 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     Error,
+    #[default]
     EOF,
 }
 
@@ -59,17 +73,14 @@ impl fmt::Display for TokenType {
     }
 }
 
-impl Default for TokenType {
-    fn default() -> Self {
-        TokenType::EOF
-    }
-}
-
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Token<'b> {
     pub typ: TokenType,
     pub src: &'b str,
     pub line: i32,
+
+    /// The bytes of the lexeme, or of what was scanned before an error.
+    pub span: Span,
 }
 
 pub struct Scanner<'a> {
@@ -82,7 +93,7 @@ pub struct Scanner<'a> {
 impl<'a> Scanner<'a> {
     pub fn new(source: &'a str) -> Self {
         Scanner {
-            source: source,
+            source,
             start: 0,
             current: 0,
             line: 1,
@@ -94,7 +105,7 @@ impl<'a> Scanner<'a> {
     /// This simulates a nul-terminated string.
     /// This method is copied from the [Rust Regex parse](https://github.com/rust-lang/regex/blob/master/regex-syntax/src/ast/parse.rs#L461).
     fn char_at(&self, i: usize) -> char {
-        self.source[i..].chars().next().unwrap_or_else(|| '\0')
+        self.source[i..].chars().next().unwrap_or('\0')
     }
 
     /// Returns the current char.
@@ -103,19 +114,11 @@ impl<'a> Scanner<'a> {
     }
 
     fn is_alpha(&self, c: char) -> bool {
-        match c {
-            'a'..='z' => true,
-            'A'..='Z' => true,
-            '_' => true,
-            _ => false,
-        }
+        matches!(c, 'a'..='z' | 'A'..='Z' | '_')
     }
 
     fn is_digit(&self, c: char) -> bool {
-        match c {
-            '0'..='9' => true,
-            _ => false,
-        }
+        c.is_ascii_digit()
     }
 
     fn is_at_end(&self) -> bool {
@@ -156,9 +159,10 @@ impl<'a> Scanner<'a> {
 
     fn make_token(&self, typ: TokenType) -> Token<'a> {
         Token {
-            typ: typ,
+            typ,
             src: &self.source[self.start..self.current],
             line: self.line,
+            span: self.span(),
         }
     }
 
@@ -167,9 +171,14 @@ impl<'a> Scanner<'a> {
             typ: TokenType::Error,
             src: message,
             line: self.line,
+            span: self.span(),
         }
     }
 
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current)
+    }
+
     fn skip_whitespace(&mut self) {
         loop {
             let c: char = self.peek();
@@ -181,14 +190,10 @@ impl<'a> Scanner<'a> {
                     self.line += 1;
                     self.advance();
                 }
-                '/' => {
-                    if self.peek_next() == '/' {
-                        // A comment goes until the end of the line.
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
-                    } else {
-                        return;
+                '/' if self.peek_next() == '/' => {
+                    // A comment goes until the end of the line.
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
                     }
                 }
                 _ => return,
@@ -197,8 +202,9 @@ impl<'a> Scanner<'a> {
     }
 
     fn check_keyword(&self, start: usize, length: usize, rest: &str, typ: TokenType) -> TokenType {
-        let left = &self.source[(self.start + start)..(self.start + start + length)];
-        if self.current - self.start == start + length && left == rest {
+        if self.current - self.start == start + length
+            && &self.source[(self.start + start)..(self.start + start + length)] == rest
+        {
             typ
         } else {
             TokenType::Identifier
@@ -208,12 +214,25 @@ impl<'a> Scanner<'a> {
     fn identifier_type(&mut self) -> TokenType {
         match self.char_at(self.start) {
             'a' => self.check_keyword(1, 2, "nd", TokenType::And),
-            'c' => self.check_keyword(1, 4, "lass", TokenType::Class),
+            'b' => self.check_keyword(1, 4, "reak", TokenType::Break),
+            'c' => {
+                if self.current - self.start > 1 {
+                    match self.char_at(self.start + 1) {
+                        'a' => self.check_keyword(2, 3, "tch", TokenType::Catch),
+                        'l' => self.check_keyword(2, 3, "ass", TokenType::Class),
+                        'o' => self.check_keyword(2, 6, "ntinue", TokenType::Continue),
+                        _ => TokenType::Identifier,
+                    }
+                } else {
+                    TokenType::Identifier
+                }
+            }
             'e' => self.check_keyword(1, 3, "lse", TokenType::Else),
             'f' => {
                 if self.current - self.start > 1 {
                     match self.char_at(self.start + 1) {
                         'a' => self.check_keyword(2, 3, "lse", TokenType::False),
+                        'i' => self.check_keyword(2, 5, "nally", TokenType::Finally),
                         'o' => self.check_keyword(2, 1, "r", TokenType::For),
                         'u' => self.check_keyword(2, 1, "n", TokenType::Fun),
                         _ => TokenType::Identifier,
@@ -222,7 +241,17 @@ impl<'a> Scanner<'a> {
                     TokenType::Identifier
                 }
             }
-            'i' => self.check_keyword(1, 1, "f", TokenType::If),
+            'i' => {
+                if self.current - self.start > 1 {
+                    match self.char_at(self.start + 1) {
+                        'f' => self.check_keyword(2, 0, "", TokenType::If),
+                        'm' => self.check_keyword(2, 4, "port", TokenType::Import),
+                        _ => TokenType::Identifier,
+                    }
+                } else {
+                    TokenType::Identifier
+                }
+            }
             'n' => self.check_keyword(1, 2, "il", TokenType::Nil),
             'o' => self.check_keyword(1, 1, "r", TokenType::Or),
             'p' => self.check_keyword(1, 4, "rint", TokenType::Print),
@@ -231,8 +260,16 @@ impl<'a> Scanner<'a> {
             't' => {
                 if self.current - self.start > 1 {
                     match self.char_at(self.start + 1) {
-                        'h' => self.check_keyword(2, 2, "is", TokenType::This),
-                        'r' => self.check_keyword(2, 2, "ue", TokenType::True),
+                        'h' => match self.check_keyword(2, 2, "is", TokenType::This) {
+                            TokenType::Identifier => {
+                                self.check_keyword(2, 3, "row", TokenType::Throw)
+                            }
+                            typ => typ,
+                        },
+                        'r' => match self.check_keyword(2, 2, "ue", TokenType::True) {
+                            TokenType::Identifier => self.check_keyword(2, 1, "y", TokenType::Try),
+                            typ => typ,
+                        },
                         _ => TokenType::Identifier,
                     }
                 } else {
@@ -274,7 +311,7 @@ impl<'a> Scanner<'a> {
 
     fn string(&mut self) -> Token<'a> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() != '\n' {
+            if self.peek() == '\n' {
                 self.line += 1;
             }
             self.advance();
@@ -312,6 +349,9 @@ impl<'a> Scanner<'a> {
             ')' => return self.make_token(TokenType::RightParen),
             '{' => return self.make_token(TokenType::LeftBrace),
             '}' => return self.make_token(TokenType::RightBrace),
+            '[' => return self.make_token(TokenType::LeftBracket),
+            ']' => return self.make_token(TokenType::RightBracket),
+            ':' => return self.make_token(TokenType::Colon),
             ';' => return self.make_token(TokenType::Semicolon),
             ',' => return self.make_token(TokenType::Comma),
             '.' => return self.make_token(TokenType::Dot),
@@ -352,10 +392,10 @@ impl<'a> Scanner<'a> {
                 return self.make_token(typ);
             }
             '"' => return self.string(),
-            _ => unimplemented!(),
+            _ => (),
         }
 
-        return self.error_token("Unexpected character.");
+        self.error_token("Unexpected character.")
     }
 }
 
@@ -366,16 +406,19 @@ mod tests {
     #[test]
     fn scan_token_type() {
         let mut scanner = Scanner::new(
-            "and class else false\r fun \nfor if nil or print return super this true var while\0",
+            "and break class continue else false\r fun \nfor if import nil or print return super this true var while\0",
         );
 
         assert_eq!(scanner.scan_token().typ, TokenType::And);
+        assert_eq!(scanner.scan_token().typ, TokenType::Break);
         assert_eq!(scanner.scan_token().typ, TokenType::Class);
+        assert_eq!(scanner.scan_token().typ, TokenType::Continue);
         assert_eq!(scanner.scan_token().typ, TokenType::Else);
         assert_eq!(scanner.scan_token().typ, TokenType::False);
         assert_eq!(scanner.scan_token().typ, TokenType::Fun);
         assert_eq!(scanner.scan_token().typ, TokenType::For);
         assert_eq!(scanner.scan_token().typ, TokenType::If);
+        assert_eq!(scanner.scan_token().typ, TokenType::Import);
         assert_eq!(scanner.scan_token().typ, TokenType::Nil);
         assert_eq!(scanner.scan_token().typ, TokenType::Or);
         assert_eq!(scanner.scan_token().typ, TokenType::Print);
@@ -386,4 +429,17 @@ mod tests {
         assert_eq!(scanner.scan_token().typ, TokenType::Var);
         assert_eq!(scanner.scan_token().typ, TokenType::While);
     }
+
+    #[test]
+    fn scan_exception_keywords() {
+        let mut scanner = Scanner::new("try throw catch finally thr tr fin\0");
+
+        assert_eq!(scanner.scan_token().typ, TokenType::Try);
+        assert_eq!(scanner.scan_token().typ, TokenType::Throw);
+        assert_eq!(scanner.scan_token().typ, TokenType::Catch);
+        assert_eq!(scanner.scan_token().typ, TokenType::Finally);
+        assert_eq!(scanner.scan_token().typ, TokenType::Identifier);
+        assert_eq!(scanner.scan_token().typ, TokenType::Identifier);
+        assert_eq!(scanner.scan_token().typ, TokenType::Identifier);
+    }
 }
@@ -1,27 +1,23 @@
 use crate::chunk::{Chunk, OpCode};
 use crate::compiler::Compiler;
 use crate::debug::disassemble_instruction;
+use crate::memory::{self, HeapSnapshot, HeapStats};
 use crate::value::{print_value, Value};
 
-macro_rules! runtime_error {
-    ( $vm:ident, $format:expr) => {{
-        eprintln!($format);
+use diagnostics::{Diagnostic, Emitter, Span};
 
-        let instruction = $vm.ip as usize - $vm.chunk.code as usize;
+macro_rules! runtime_error {
+    ( $vm:ident, $format:expr $(, $arg:expr)* ) => {{
+        let instruction = unsafe { $vm.ip.offset_from($vm.chunk.code) } as usize - 1;
         let line = $vm.chunk.lines[instruction];
-        eprintln!("[line {:>4}] in script", line);
 
-        $vm.reset_stack();
-    }};
-    ( $vm:ident, $format:expr, $( $arg:expr),* ) => {{
-        eprintln!($format, $( $arg ),*);
-
-        let instruction = $vm.ip as usize - $vm.chunk.code as usize;
-        let line = $vm.chunk.lines[instruction];
-        eprintln!("[line {:>4}] in script", line);
+        // Instructions only know their line, so the whole line is marked.
+        let span = Span::of_line(&$vm.source, line as usize);
+        let diagnostic = Diagnostic::error(&format!($format $(, $arg)*)).with_primary(span, "");
+        $vm.emitter.emit(&diagnostic, &$vm.source);
 
         $vm.reset_stack();
-    }}
+    }};
 }
 
 macro_rules! binary_op{
@@ -45,6 +41,12 @@ pub struct VM {
     chunk: Chunk,
     ip: *const OpCode,
     stack: Vec<Value>,
+
+    /// Reports compile and runtime errors.
+    emitter: Emitter,
+
+    /// The source being interpreted, which runtime errors point into.
+    source: String,
 }
 
 // TODO: replace with Result<_, Error>
@@ -59,28 +61,52 @@ impl VM {
         let chunk = Chunk::new();
         let ip = chunk.code;
         VM {
-            chunk: chunk,
-            ip: ip,
+            chunk,
+            ip,
             stack: Vec::with_capacity(STACK_MAX),
+            emitter: Emitter::default(),
+            source: String::new(),
         }
     }
 
+    pub fn set_emitter(&mut self, emitter: Emitter) {
+        self.emitter = emitter;
+    }
+
     pub fn interpret(&mut self, source: &str) -> InterpretResult {
-        let mut compiler = Compiler::new();
+        let mut compiler = Compiler::new(self.emitter.clone());
+        // Sources end with a nul so the scanner can peek past the end.
+        self.source = source.trim_end_matches('\0').to_string();
 
         if let Some(chunk) = compiler.compile(source) {
+            // TODO: keep globals, interned strings and functions across REPL entries once the VM has
+            // them (chapter 21). Until then every entry simply replaces the chunk.
             self.chunk = chunk;
             self.ip = self.chunk.code;
 
             let result = self.run();
 
+            if cfg!(feature = "debug_log_heap") {
+                eprintln!("{}", self.heap_stats());
+            }
+
             // TODO: free chunk
             result
         } else {
-            return InterpretResult::CompileError;
+            InterpretResult::CompileError
         }
     }
 
+    /// Returns the object counts and bytes per kind of everything currently allocated.
+    pub fn heap_stats(&self) -> HeapStats {
+        memory::heap_stats()
+    }
+
+    /// Returns every live allocation, with the chunk that ran last as the root.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        memory::heap_snapshot(&[self.chunk.code as *const OpCode])
+    }
+
     fn run(&mut self) -> InterpretResult {
         let mut position: usize = 0; // TODO: infer position from self.ip.
         loop {
@@ -0,0 +1,515 @@
+//! Error reporting shared by the tree-walk interpreter and the bytecode VM.
+//!
+//! A `Diagnostic` points into the source with byte spans. It is rendered either for humans, with
+//! the offending lines and a `^^^` underline, or as one JSON object per line for editors. A span
+//! names the source it belongs to, so one diagnostic can point into several files, e.g. an error
+//! in a module and the import that ran it.
+
+use std::fmt::{self, Write as _};
+use std::io::{self, IsTerminal, Write as _};
+
+/// A range of bytes in a source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub source: SourceId,
+}
+
+impl Span {
+    /// A span in the first source, which is the only one of single file programs.
+    pub fn new(start: usize, end: usize) -> Self {
+        Span {
+            start,
+            end,
+            source: SourceId::default(),
+        }
+    }
+}
+
+impl Span {
+    /// The span of a whole 1-based line, for errors that only know their line.
+    pub fn of_line(source: &str, line: usize) -> Self {
+        let (start, text) = source_line(source, line);
+        Span::new(start, start + text.len())
+    }
+}
+
+/// The index of a source in `Sources`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceId(pub usize);
+
+/// The files diagnostics point into and their text.
+#[derive(Debug, Default, Clone)]
+pub struct Sources {
+    sources: Vec<(String, String)>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Holds only `text` from `file`, for programs that are a single file.
+    pub fn single(file: &str, text: &str) -> Self {
+        let mut sources = Sources::new();
+        sources.add(file, text);
+        sources
+    }
+
+    pub fn add(&mut self, file: &str, text: &str) -> SourceId {
+        self.sources.push((file.to_string(), text.to_string()));
+        SourceId(self.sources.len() - 1)
+    }
+
+    /// The file name and text of a source. Unknown sources are empty.
+    pub fn get(&self, id: SourceId) -> (&str, &str) {
+        self.sources
+            .get(id.0)
+            .map_or(("", ""), |(file, text)| (file, text))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown error format '{}'.", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    #[default]
+    Error,
+
+    /// Code that runs but is probably wrong. Only lints report warnings.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+
+    /// Where the error is. Errors without a location only print their message.
+    pub primary: Option<Label>,
+
+    /// Related places, e.g. where a variable was declared.
+    pub secondary: Vec<Label>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.to_string(),
+            primary: None,
+            secondary: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: &str) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message)
+        }
+    }
+
+    pub fn with_primary(mut self, span: Span, message: &str) -> Self {
+        self.primary = Some(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    /// Renders the diagnostic for `source`, which is read from `file`.
+    pub fn render(&self, file: &str, source: &str, format: Format, colour: bool) -> String {
+        self.render_in(file, &Sources::single(file, source), format, colour)
+    }
+
+    /// Renders the diagnostic with every label against the source its span belongs to.
+    /// Diagnostics without a location name `file`.
+    pub fn render_in(&self, file: &str, sources: &Sources, format: Format, colour: bool) -> String {
+        match format {
+            Format::Human => self.render_human(sources, colour),
+            Format::Json => self.render_json(file, sources),
+        }
+    }
+
+    fn render_human(&self, sources: &Sources, colour: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if colour {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let colour_code = match self.severity {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+        };
+        let mut out = format!(
+            "{}: {}\n",
+            paint(colour_code, &self.severity.to_string()),
+            paint("1", &self.message)
+        );
+        let primary = match &self.primary {
+            Some(primary) => primary,
+            None => return out,
+        };
+
+        let mut labels: Vec<(&Label, bool)> = std::iter::once((primary, true))
+            .chain(self.secondary.iter().map(|label| (label, false)))
+            .collect();
+        labels.sort_by_key(|(label, _)| label.span.start);
+        let line_of = |label: &Label| {
+            let (_, source) = sources.get(label.span.source);
+            Position::new(source, label.span.start).line
+        };
+
+        // The source of the primary label comes first, then the others in the order they are used.
+        let mut ids = vec![primary.span.source];
+        for label in &self.secondary {
+            if !ids.contains(&label.span.source) {
+                ids.push(label.span.source);
+            }
+        }
+        let width = labels
+            .iter()
+            .map(|(label, _)| line_of(label))
+            .max()
+            .unwrap_or(1)
+            .to_string()
+            .len();
+        let gutter = paint("1;34", &format!("{:width$} |", "", width = width));
+
+        for (i, id) in ids.into_iter().enumerate() {
+            let (file, source) = sources.get(id);
+            let labels: Vec<&(&Label, bool)> = labels
+                .iter()
+                .filter(|(label, _)| label.span.source == id)
+                .collect();
+            let (first, arrow) = match i {
+                0 => (primary, "-->"),
+                _ => (labels[0].0, ":::"),
+            };
+            let position = Position::new(source, first.span.start);
+            let _ = writeln!(
+                out,
+                "{}{} {}:{}:{}",
+                " ".repeat(width),
+                paint("1;34", arrow),
+                file,
+                position.line,
+                position.column
+            );
+            let _ = writeln!(out, "{}", gutter);
+
+            let mut lines: Vec<usize> = labels.iter().map(|(label, _)| line_of(label)).collect();
+            lines.sort_unstable();
+            lines.dedup();
+            for line in lines {
+                let (start, text) = source_line(source, line);
+                let number = paint("1;34", &format!("{:>width$} |", line, width = width));
+                let _ = writeln!(out, "{} {}", number, text);
+
+                for (label, is_primary) in &labels {
+                    let label_start = label.span.start.clamp(start, start + text.len());
+                    if line_of(label) != line {
+                        continue;
+                    }
+
+                    // Keep tabs so the underline lines up with the text above it.
+                    let indent: String = text[..label_start - start]
+                        .chars()
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+                    let end = label.span.end.clamp(label_start, start + text.len());
+                    let length = text[label_start - start..end - start]
+                        .chars()
+                        .count()
+                        .max(1);
+                    let (mark, code) = if *is_primary {
+                        ('^', colour_code)
+                    } else {
+                        ('-', "1;34")
+                    };
+                    let underline =
+                        format!("{} {}", mark.to_string().repeat(length), label.message);
+                    let _ = writeln!(
+                        out,
+                        "{} {}{}",
+                        gutter,
+                        indent,
+                        paint(code, underline.trim_end())
+                    );
+                }
+            }
+        }
+
+        out
+    }
+
+    fn render_json(&self, file: &str, sources: &Sources) -> String {
+        let label = |label: &Label| {
+            let (file, source) = sources.get(label.span.source);
+            let position = Position::new(source, label.span.start);
+            format!(
+                "{{\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}}}",
+                json_string(&label.message),
+                json_string(file),
+                position.line,
+                position.column,
+                label.span.start,
+                label.span.end
+            )
+        };
+
+        let file = match &self.primary {
+            Some(primary) => sources.get(primary.span.source).0,
+            None => file,
+        };
+        let primary = self.primary.as_ref().map(label);
+        let secondary: Vec<String> = self.secondary.iter().map(label).collect();
+        format!(
+            "{{\"severity\":\"{}\",\"message\":{},\"file\":{},\"primary\":{},\"secondary\":[{}]}}\n",
+            self.severity,
+            json_string(&self.message),
+            json_string(file),
+            primary.as_deref().unwrap_or("null"),
+            secondary.join(",")
+        )
+    }
+}
+
+/// Where errors go and how they look.
+#[derive(Debug, Default, Clone)]
+pub struct Emitter {
+    pub file: String,
+    pub format: Format,
+}
+
+impl Emitter {
+    pub fn new(file: &str, format: Format) -> Self {
+        Emitter {
+            file: file.to_string(),
+            format,
+        }
+    }
+
+    /// Writes the diagnostic to stderr, in colour if stderr is a terminal.
+    pub fn emit(&self, diagnostic: &Diagnostic, source: &str) {
+        self.emit_in(diagnostic, &Sources::single(&self.file, source));
+    }
+
+    /// Like `emit` for diagnostics whose spans point into several sources. Only diagnostics
+    /// without a location name `file`.
+    pub fn emit_in(&self, diagnostic: &Diagnostic, sources: &Sources) {
+        let colour = self.format == Format::Human && io::stderr().is_terminal();
+        let rendered = diagnostic.render_in(&self.file, sources, self.format, colour);
+        let _ = io::stderr().write_all(rendered.as_bytes());
+    }
+}
+
+/// A 1-based line and column, counted in chars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// Finds the position of a byte offset. Offsets past the end point to the end.
+    pub fn new(source: &str, offset: usize) -> Self {
+        let mut offset = offset.min(source.len());
+        while !source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// Returns the byte offset and the text of a 1-based line, without the line break.
+fn source_line(source: &str, line: usize) -> (usize, &str) {
+    let start = if line <= 1 {
+        0
+    } else {
+        source
+            .match_indices('\n')
+            .nth(line - 2)
+            .map_or(source.len(), |(i, _)| i + 1)
+    };
+    let end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    (start, source[start..end].trim_end_matches('\r'))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "var a = 1;\nvar a = \"é\" + b;\n";
+
+    #[test]
+    fn render_human() {
+        let diagnostic = Diagnostic::error("Already declared.")
+            .with_primary(Span::new(15, 16), "")
+            .with_secondary(Span::new(4, 5), "declared here");
+
+        assert_eq!(
+            diagnostic.render("test.lox", SOURCE, Format::Human, false),
+            "error: Already declared.\n \
+             --> test.lox:2:5\n  \
+              |\n\
+             1 | var a = 1;\n  \
+              |     - declared here\n\
+             2 | var a = \"é\" + b;\n  \
+              |     ^\n"
+        );
+    }
+
+    #[test]
+    fn render_warning() {
+        let diagnostic =
+            Diagnostic::warning("Unused variable 'a'.").with_primary(Span::new(4, 5), "");
+
+        assert_eq!(
+            diagnostic.render("test.lox", SOURCE, Format::Human, false),
+            "warning: Unused variable 'a'.\n \
+             --> test.lox:1:5\n  \
+              |\n\
+             1 | var a = 1;\n  \
+              |     ^\n"
+        );
+        assert!(diagnostic
+            .render("test.lox", SOURCE, Format::Json, false)
+            .starts_with("{\"severity\":\"warning\","));
+    }
+
+    #[test]
+    fn render_labels_in_several_sources() {
+        let mut sources = Sources::new();
+        let main = sources.add("main.lox", "import \"lib.lox\";\n");
+        let lib = sources.add("lib.lox", "\nvar x = 1 + nil;\n");
+        let diagnostic = Diagnostic::error("Operands must be numbers.")
+            .with_primary(
+                Span {
+                    start: 11,
+                    end: 12,
+                    source: lib,
+                },
+                "",
+            )
+            .with_secondary(
+                Span {
+                    start: 0,
+                    end: 6,
+                    source: main,
+                },
+                "imported here",
+            );
+
+        assert_eq!(
+            diagnostic.render_in("main.lox", &sources, Format::Human, false),
+            "error: Operands must be numbers.\n \
+             --> lib.lox:2:11\n  \
+              |\n\
+             2 | var x = 1 + nil;\n  \
+              |           ^\n \
+             ::: main.lox:1:1\n  \
+              |\n\
+             1 | import \"lib.lox\";\n  \
+              | ------ imported here\n"
+        );
+        assert!(diagnostic
+            .render_in("main.lox", &sources, Format::Json, false)
+            .contains("\"secondary\":[{\"message\":\"imported here\",\"file\":\"main.lox\","));
+    }
+
+    #[test]
+    fn render_line_only() {
+        let diagnostic = Diagnostic::error("Operands must be numbers.")
+            .with_primary(Span::of_line(SOURCE, 2), "");
+
+        assert_eq!(diagnostic.primary.as_ref().unwrap().span, Span::new(11, 28));
+        assert!(diagnostic
+            .render("test.lox", SOURCE, Format::Json, false)
+            .contains("\"line\":2,\"column\":1,"));
+    }
+
+    #[test]
+    fn render_json() {
+        // The span of `b` starts after the two byte é, but its column counts chars.
+        let diagnostic = Diagnostic::error("Undefined \"b\".").with_primary(Span::new(26, 27), "");
+
+        assert_eq!(
+            diagnostic.render("test.lox", SOURCE, Format::Json, false),
+            "{\"severity\":\"error\",\"message\":\"Undefined \\\"b\\\".\",\"file\":\"test.lox\",\
+             \"primary\":{\"message\":\"\",\"file\":\"test.lox\",\"line\":2,\"column\":15,\"span\":{\"start\":26,\"end\":27}},\
+             \"secondary\":[]}\n"
+        );
+    }
+}
@@ -9,30 +9,36 @@ fn main() {
     let path = Path::new(&env::var("OUT_DIR").unwrap()).join("keywords.rs");
     let mut file = BufWriter::new(File::create(&path).unwrap());
 
-    write!(
+    writeln!(
         &mut file,
-        "pub static KEYWORDS: phf::Map<&'static str, TokenType> =
-"
+        "pub static KEYWORDS: phf::Map<&'static str, TokenType> ="
     )
     .unwrap();
     phf_codegen::Map::new()
         .entry("and", "TokenType::And")
+        .entry("break", "TokenType::Break")
+        .entry("catch", "TokenType::Catch")
         .entry("class", "TokenType::Class")
+        .entry("continue", "TokenType::Continue")
         .entry("else", "TokenType::Else")
         .entry("false", "TokenType::False")
+        .entry("finally", "TokenType::Finally")
         .entry("fun", "TokenType::Fun")
         .entry("for", "TokenType::For")
         .entry("if", "TokenType::If")
+        .entry("import", "TokenType::Import")
         .entry("nil", "TokenType::Nil")
         .entry("or", "TokenType::Or")
         .entry("print", "TokenType::Print")
         .entry("return", "TokenType::Return")
         .entry("super", "TokenType::Super")
         .entry("this", "TokenType::This")
+        .entry("throw", "TokenType::Throw")
         .entry("true", "TokenType::True")
+        .entry("try", "TokenType::Try")
         .entry("var", "TokenType::Var")
         .entry("while", "TokenType::While")
         .build(&mut file)
         .unwrap();
-    write!(&mut file, ";\n").unwrap();
+    writeln!(&mut file, ";").unwrap();
 }
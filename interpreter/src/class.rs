@@ -12,18 +12,54 @@ pub struct LoxClass {
     pub name: String,
     pub superclass: Option<Rc<RefCell<LoxClass>>>,
     pub methods: HashMap<String, Function>,
+    pub getters: HashMap<String, Function>,
+    pub setters: HashMap<String, Function>,
+
+    /// Methods declared with `class`. They are called on the class, which is their `this`.
+    pub class_methods: HashMap<String, Function>,
 }
 
 impl LoxClass {
+    /// A class without methods yet.
+    pub fn new(name: &str, superclass: Option<Rc<RefCell<LoxClass>>>) -> Self {
+        LoxClass {
+            name: name.to_string(),
+            superclass,
+            methods: HashMap::new(),
+            getters: HashMap::new(),
+            setters: HashMap::new(),
+            class_methods: HashMap::new(),
+        }
+    }
+
     pub fn find_method(&self, name: &str) -> Option<Function> {
-        if self.methods.contains_key(name) {
-            self.methods.get(name).map(|f| f.clone())
+        self.find(name, |class| &class.methods)
+    }
+
+    pub fn find_getter(&self, name: &str) -> Option<Function> {
+        self.find(name, |class| &class.getters)
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<Function> {
+        self.find(name, |class| &class.setters)
+    }
+
+    pub fn find_class_method(&self, name: &str) -> Option<Function> {
+        self.find(name, |class| &class.class_methods)
+    }
+
+    /// Looks up a function in a table of this class or else of its superclasses.
+    fn find(
+        &self,
+        name: &str,
+        table: fn(&LoxClass) -> &HashMap<String, Function>,
+    ) -> Option<Function> {
+        if let Some(function) = table(self).get(name) {
+            Some(function.clone())
+        } else if let Some(ref superclass) = self.superclass {
+            superclass.borrow().find(name, table)
         } else {
-            if let Some(ref superclass) = self.superclass {
-                superclass.borrow().find_method(name)
-            } else {
-                None
-            }
+            None
         }
     }
 }
@@ -36,6 +72,7 @@ pub struct LoxInstance {
 
 impl LoxInstance {
     /// Returns a new `LoxInstance` wrapped in an `Object::Instance`.
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(class: &Rc<RefCell<LoxClass>>) -> Object {
         let instance = LoxInstance {
             class: Rc::clone(class),
@@ -7,10 +7,22 @@ use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
 
+/// An environment holds the variables of one scope.
+///
+/// Local variables are stored in slots. The `Resolver` assigns each local the slot index in the
+/// order it is declared in its scope, which is the order the interpreter defines them. Only the
+/// global environment, the one without an enclosing environment, stores its values by name.
 #[derive(Debug)]
 pub struct Environment {
     pub enclosing: Option<Rc<RefCell<Environment>>>, // Parent
     values: HashMap<String, Object>,
+    slots: Vec<Object>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
@@ -18,6 +30,7 @@ impl Environment {
         Environment {
             enclosing: None,
             values: HashMap::new(),
+            slots: Vec::new(),
         }
     }
 
@@ -25,11 +38,25 @@ impl Environment {
         Environment {
             enclosing: Some(Rc::clone(enclosing)),
             values: HashMap::new(),
+            slots: Vec::new(),
         }
     }
 
+    /// Defines a global by name or the next local slot.
     pub fn define(&mut self, name: String, value: Object) {
-        self.values.insert(name, value);
+        if self.enclosing.is_some() {
+            self.slots.push(value);
+        } else {
+            self.values.insert(name, value);
+        }
+    }
+
+    /// Returns the names and values of a global environment.
+    pub fn globals(&self) -> Vec<(String, Object)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
     }
 
     fn ancestor(&self, distance: usize) -> Rc<RefCell<Environment>> {
@@ -37,7 +64,7 @@ impl Environment {
         let parent = self
             .enclosing
             .clone()
-            .expect(&format!("No enclosing environment at {}", 1));
+            .unwrap_or_else(|| panic!("No enclosing environment at {}", 1));
         let mut environment = Rc::clone(&parent);
 
         // Get next ancestors
@@ -46,38 +73,25 @@ impl Environment {
                 .borrow()
                 .enclosing
                 .clone()
-                .expect(&format!("No enclosing environment at {}", i));
+                .unwrap_or_else(|| panic!("No enclosing environment at {}", i));
             environment = Rc::clone(&parent);
         }
         environment
     }
 
-    pub fn get_at(&self, distance: usize, name: &str) -> Result<Object, Error> {
+    pub fn get_at(&self, distance: usize, slot: usize) -> Result<Object, Error> {
         if distance > 0 {
-            Ok(self
-                .ancestor(distance)
-                .borrow()
-                .values
-                .get(name)
-                .expect(&format!("Undefined variable '{}'", name))
-                .clone())
+            Ok(self.ancestor(distance).borrow().slots[slot].clone())
         } else {
-            Ok(self
-                .values
-                .get(name)
-                .expect(&format!("Undefined variable '{}'", name))
-                .clone())
+            Ok(self.slots[slot].clone())
         }
     }
 
-    pub fn assign_at(&mut self, distance: usize, name: &Token, value: Object) -> Result<(), Error> {
+    pub fn assign_at(&mut self, distance: usize, slot: usize, value: Object) -> Result<(), Error> {
         if distance > 0 {
-            self.ancestor(distance)
-                .borrow_mut()
-                .values
-                .insert(name.lexeme.clone(), value);
+            self.ancestor(distance).borrow_mut().slots[slot] = value;
         } else {
-            self.values.insert(name.lexeme.clone(), value);
+            self.slots[slot] = value;
         }
         Ok(())
     }
@@ -87,37 +101,29 @@ impl Environment {
         if let Some(value) = self.values.get(key) {
             Ok((*value).clone())
         } else {
-            if let Some(ref enclosing) = self.enclosing {
-                enclosing.borrow().get(name)
-            } else {
-                Err(Error::Runtime {
-                    token: name.clone(),
-                    message: format!("Undefined variable '{}'.", key),
-                })
-            }
+            Err(Error::Runtime {
+                token: name.clone(),
+                message: format!("Undefined variable '{}'.", key),
+            })
         }
     }
 
     pub fn assign(&mut self, name: &Token, value: Object) -> Result<(), Error> {
         let key = &*name.lexeme;
-        if self.values.contains_key(key) {
-            self.values.insert(name.lexeme.clone(), value);
+        if let Some(variable) = self.values.get_mut(key) {
+            *variable = value;
             Ok(())
         } else {
-            if let Some(ref enclosing) = self.enclosing {
-                enclosing.borrow_mut().assign(name, value)
-            } else {
-                Err(Error::Runtime {
-                    token: name.clone(),
-                    message: format!("Undefined variable '{}'", key),
-                })
-            }
+            Err(Error::Runtime {
+                token: name.clone(),
+                message: format!("Undefined variable '{}'", key),
+            })
         }
     }
 }
 
 impl fmt::Display for Environment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "values: {:?}", self.values)
+        write!(f, "values: {:?} slots: {:?}", self.values, self.slots)
     }
 }
@@ -1,24 +1,151 @@
+use std::cell::RefCell;
 use std::convert;
 use std::fmt;
 use std::io;
 
+use diagnostics::{Diagnostic, Emitter, Format, SourceId, Sources};
+
 use crate::object::Object;
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
+
+thread_local! {
+    /// Where errors are reported, every source their spans point into and the one being scanned.
+    static SOURCES: RefCell<(Emitter, Sources, SourceId)> = RefCell::new(Default::default());
+
+    /// Diagnostics that `collect` keeps instead of printing them.
+    static COLLECTED: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+pub fn set_format(format: Format) {
+    SOURCES.with(|sources| sources.borrow_mut().0.format = format);
+}
+
+/// Adds `text` from `file` as the source the scanner reads next and returns the previous one.
+///
+/// Earlier sources are kept, so errors in functions declared there, e.g. in an imported module,
+/// still point into the right file.
+pub fn set_source(file: &str, text: &str) -> SourceId {
+    SOURCES.with(|sources| {
+        let (emitter, sources, current) = &mut *sources.borrow_mut();
+        emitter.file = file.to_string();
+        std::mem::replace(current, sources.add(file, text))
+    })
+}
+
+/// Makes an earlier source the one the scanner reads, e.g. the importer after a module.
+pub fn restore_source(id: SourceId) {
+    SOURCES.with(|sources| {
+        let (emitter, sources, current) = &mut *sources.borrow_mut();
+        emitter.file = sources.get(id).0.to_string();
+        *current = id;
+    })
+}
 
-pub fn error(line: i32, message: &str) {
-    report(line, "", message);
+/// The source that new tokens belong to.
+pub fn current_source() -> SourceId {
+    SOURCES.with(|sources| sources.borrow().2)
 }
 
-pub fn report(line: i32, where_: &str, message: &str) {
-    eprintln!("[line {}] Error{}: {}", line, where_, message);
+/// Runs `f` and returns the diagnostics it reported instead of printing them, e.g. for editors.
+pub fn collect<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let previous = COLLECTED.with(|collected| collected.replace(Some(Vec::new())));
+    let result = f();
+    let diagnostics = COLLECTED.with(|collected| collected.replace(previous));
+    (result, diagnostics.unwrap_or_default())
+}
+
+pub fn report(diagnostic: &Diagnostic) {
+    let collected = COLLECTED.with(|collected| match &mut *collected.borrow_mut() {
+        Some(diagnostics) => {
+            diagnostics.push(diagnostic.clone());
+            true
+        }
+        None => false,
+    });
+    if collected {
+        return;
+    }
+
+    SOURCES.with(|sources| {
+        let (emitter, sources, _) = &*sources.borrow();
+        emitter.emit_in(diagnostic, sources);
+    });
     // had_error = true; TODO: Use custom Error type
 }
 
-pub fn parser_error(token: &Token, message: &str) {
-    if token.tpe == TokenType::EOF {
-        report(token.line, " at end", message);
+/// An error at a token. Tokens that do not come from the source have no location.
+pub fn at(token: &Token, message: &str) -> Diagnostic {
+    let diagnostic = Diagnostic::error(message);
+    if token.column == 0 {
+        diagnostic
     } else {
-        report(token.line, &format!(" at '{}'", token.lexeme), message);
+        diagnostic.with_primary(token.span, "")
+    }
+}
+
+pub fn error(span: Span, message: &str) {
+    report(&Diagnostic::error(message).with_primary(span, ""));
+}
+
+/// Reports every syntax error of a source.
+pub fn parse_errors(errors: &[ParseError]) -> Error {
+    for error in errors {
+        report(&error.diagnostic());
+    }
+    Error::Parse
+}
+
+/// Reports an error that stopped the program.
+pub fn runtime_error(error: &Error) {
+    report(&runtime_diagnostic(error));
+}
+
+/// Errors in modules point into the module. The imports that ran it are secondary labels.
+fn runtime_diagnostic(error: &Error) -> Diagnostic {
+    match error {
+        Error::Runtime { token, message } => at(token, message),
+        Error::Throw { token, value } => at(token, &format!("Uncaught {}", value)),
+        Error::Native { message } => Diagnostic::error(message),
+        Error::Io(e) => Diagnostic::error(&e.to_string()),
+        Error::Import { keyword, error } => {
+            runtime_diagnostic(error).with_secondary(keyword.span, "imported here")
+        }
+        other => Diagnostic::error(&other.to_string()),
+    }
+}
+
+/// A syntax error found by the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub token: Token,
+    pub span: Span,
+    pub message: String,
+
+    /// The token the parser was looking for, if it wanted a particular one.
+    pub expected: Option<TokenType>,
+
+    /// What was there instead, e.g. `'x'` or `end of file`.
+    pub found: String,
+}
+
+impl ParseError {
+    pub fn new(token: &Token, message: &str, expected: Option<TokenType>) -> Self {
+        let found = if token.tpe == TokenType::EOF {
+            "end of file".to_string()
+        } else {
+            format!("'{}'", token.lexeme)
+        };
+        ParseError {
+            token: token.clone(),
+            span: token.span,
+            message: message.to_string(),
+            expected,
+            found,
+        }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(&self.message).with_primary(self.span, &format!("found {}", self.found))
     }
 }
 
@@ -26,8 +153,27 @@ pub fn parser_error(token: &Token, message: &str) {
 pub enum Error {
     Io(io::Error),
     Parse,
-    Return { value: Object },
-    Runtime { token: Token, message: String },
+    Runtime {
+        token: Token,
+        message: String,
+    },
+
+    /// A value thrown by `throw` that no `catch` handled yet.
+    Throw {
+        token: Token,
+        value: Box<Object>,
+    },
+
+    /// A native function failed. The call expression turns it into `Runtime` with its location.
+    Native {
+        message: String,
+    },
+
+    /// An error while running a module, and the `import` of the module.
+    Import {
+        keyword: Token,
+        error: Box<Error>,
+    },
 }
 
 impl fmt::Display for Error {
@@ -35,8 +181,11 @@ impl fmt::Display for Error {
         match self {
             Error::Io(underlying) => write!(f, "IoError {}", underlying),
             Error::Parse => write!(f, "ParseError"),
-            Error::Return { value } => write!(f, "Return {:?}", value),
-            Error::Runtime { message, .. } => write!(f, "RuntimeError {}", message),
+            Error::Throw { value, .. } => write!(f, "Uncaught {}", value),
+            Error::Import { error, .. } => write!(f, "{}", error),
+            Error::Runtime { message, .. } | Error::Native { message } => {
+                write!(f, "RuntimeError {}", message)
+            }
         }
     }
 }
@@ -0,0 +1,784 @@
+use crate::error::{self, Error};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::syntax::{expr, Expr, ExprId, FunctionKind, LiteralValue, Stmt, Trivia};
+use crate::token::{Comment, Span, Token};
+
+const INDENT: &str = "  ";
+
+/// Formats the source with two space indents, one statement per line. Comments and single blank
+/// lines between declarations are kept, and comments inside statements stay after the part they
+/// follow.
+///
+/// Errors are reported already, so they are all `Error::Parse`.
+pub fn format_source(source: &str) -> Result<String, Error> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens().clone();
+    if scanner.had_error {
+        return Err(Error::Parse);
+    }
+
+    let statements = Parser::with_trivia(&tokens, &scanner.comments)
+        .parse()
+        .map_err(|errors| error::parse_errors(&errors))?;
+    let mut formatter = Formatter::new(source);
+    formatter.statements(&statements)?;
+    Ok(formatter.out)
+}
+
+struct Formatter<'s> {
+    source: &'s str,
+    out: String,
+    depth: usize,
+
+    /// Whether the next line continues the last one, like `else` after `}`.
+    join: bool,
+
+    /// The depths of the class bodies being formatted. Fields there have no `var`.
+    class_bodies: Vec<usize>,
+
+    /// The comments inside the statement being formatted that have no place yet.
+    comments: Vec<Comment>,
+}
+
+impl<'s> Formatter<'s> {
+    fn new(source: &'s str) -> Self {
+        Formatter {
+            source,
+            out: String::new(),
+            depth: 0,
+            join: false,
+            class_bodies: Vec::new(),
+            comments: Vec::new(),
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        if self.join {
+            self.out.pop();
+            self.out.push(' ');
+            self.join = false;
+        } else {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    /// Appends the comments to the last line.
+    fn trail(&mut self, comments: &[String]) {
+        if !comments.is_empty() {
+            self.join = true;
+            self.line(&comments.join(" "));
+        }
+    }
+
+    /// Removes the comments that go in a place and returns their text.
+    fn take_comments(&mut self, place: impl Fn(&Comment) -> bool) -> Vec<String> {
+        let (taken, rest): (Vec<Comment>, Vec<Comment>) = std::mem::take(&mut self.comments)
+            .into_iter()
+            .partition(place);
+        self.comments = rest;
+        taken.into_iter().map(|comment| comment.text).collect()
+    }
+
+    /// The offset of the first token from `offset` on that is not one of `chars`.
+    fn skip(&self, mut offset: usize, chars: &str) -> usize {
+        loop {
+            let rest = &self.source[offset..];
+            if rest.starts_with("//") {
+                offset += rest.find('\n').unwrap_or(rest.len());
+                continue;
+            }
+            match rest.chars().next() {
+                Some(c) if c.is_whitespace() || chars.contains(c) => offset += c.len_utf8(),
+                _ => return offset,
+            }
+        }
+    }
+
+    /// Whether the statements being formatted are the members of a class.
+    fn in_class_body(&self) -> bool {
+        self.class_bodies.last() == Some(&self.depth)
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        // Blank lines only separate statements.
+        let start = statements.iter().position(|s| !is_blank(s));
+        let end = statements.iter().rposition(|s| !is_blank(s));
+        if let (Some(start), Some(end)) = (start, end) {
+            let statements = &statements[start..=end];
+            for (i, statement) in statements.iter().enumerate() {
+                if is_inner_comment(statement) {
+                    continue;
+                }
+
+                // The parser puts the comments inside a statement right after it.
+                let comments = statements[i + 1..]
+                    .iter()
+                    .map_while(|statement| match statement {
+                        Stmt::Trivia {
+                            trivia: Trivia::InnerComment(comment),
+                        } => Some(comment.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                let outer = std::mem::replace(&mut self.comments, comments);
+                self.statement(statement)?;
+                // Comments that have no better place go on the last line of the statement.
+                let rest = std::mem::replace(&mut self.comments, outer);
+                self.trail(&rest.into_iter().map(|c| c.text).collect::<Vec<_>>());
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> Result<(), Error> {
+        match statement {
+            Stmt::Block { statements, .. } => match statements.as_slice() {
+                [initializer, Stmt::While {
+                    condition,
+                    body,
+                    increment,
+                    for_loop: true,
+                    ..
+                }] => self.for_loop(Some(initializer), condition, body, increment)?,
+                _ => self.braced("", &[], statements)?,
+            },
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let mut header = format!("class {}", name.lexeme);
+                if let Some(superclass) = superclass {
+                    header.push_str(&format!(" < {}", self.expression(superclass)?));
+                }
+                let comments = self.take_comments(|_| true);
+                self.class_bodies.push(self.depth + 1);
+                self.braced(&header, &comments, methods)?;
+                self.class_bodies.pop();
+            }
+            Stmt::Function {
+                name,
+                params,
+                body,
+                signature,
+                kind,
+                ..
+            } => {
+                let params: Vec<(&Token, Option<&Token>)> = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| (param, signature.params.get(i).and_then(Option::as_ref)))
+                    .collect();
+                let span = |(param, annotation): &(&Token, Option<&Token>)| {
+                    let end = annotation.map_or(param.span.end, |annotation| annotation.span.end);
+                    Some(Span { end, ..param.span })
+                };
+                let close = match params.last().and_then(span) {
+                    Some(span) => self.skip(span.end, ","),
+                    None => 0,
+                };
+                let params = self.items(
+                    ("(", ")"),
+                    close,
+                    &params,
+                    span,
+                    |_, (param, annotation)| Ok(typed(param, *annotation)),
+                )?;
+                let mut header = match kind {
+                    FunctionKind::Function => format!("fun {}{}", name.lexeme, params),
+                    FunctionKind::Method => format!("{}{}", name.lexeme, params),
+                    FunctionKind::ClassMethod => format!("class {}{}", name.lexeme, params),
+                    FunctionKind::Getter => name.lexeme.clone(),
+                    FunctionKind::Setter => format!("set {}{}", name.lexeme, params),
+                };
+                if let Some(returns) = &signature.returns {
+                    header.push_str(&format!(": {}", returns.lexeme));
+                }
+                let comments = self.take_comments(|_| true);
+                self.braced(&header, &comments, body)?;
+            }
+            Stmt::If {
+                condition,
+                else_branch,
+                then_branch,
+                ..
+            } => self.if_statement("if", condition, then_branch, else_branch)?,
+            Stmt::Trivia { trivia } => match trivia {
+                Trivia::BlankLine => self.out.push('\n'),
+                Trivia::Comment(text) => self.line(text),
+                Trivia::TrailingComment(text) => {
+                    self.join = !self.out.is_empty();
+                    self.line(text);
+                }
+                // `statements` places them.
+                Trivia::InnerComment(_) => {}
+            },
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.braced("try", &[], body)?;
+                if let Some((name, body)) = catch {
+                    self.join = true;
+                    self.braced(&format!("catch ({})", name.lexeme), &[], body)?;
+                }
+                if let Some(body) = finally {
+                    self.join = true;
+                    self.braced("finally", &[], body)?;
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                for_loop: true,
+                ..
+            } => self.for_loop(None, condition, body, increment)?,
+            Stmt::While {
+                condition, body, ..
+            } => {
+                let header = format!("while ({})", self.expression(condition)?);
+                self.clause(&header, body)?;
+            }
+            _ => {
+                let text = self.simple(statement)?;
+                self.line(&text);
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats a statement that fits on one line.
+    fn simple(&mut self, statement: &Stmt) -> Result<String, Error> {
+        Ok(match statement {
+            Stmt::Break { .. } => "break;".to_string(),
+            Stmt::Continue { .. } => "continue;".to_string(),
+            Stmt::Expression { expression } => format!("{};", self.expression(expression)?),
+            Stmt::Import { path, name, .. } => match name {
+                Some(name) => format!("import \"{}\" as {};", path, name.lexeme),
+                None => format!("import \"{}\";", path),
+            },
+            Stmt::Print { expression, .. } => format!("print {};", self.expression(expression)?),
+            Stmt::Return { value: None, .. } => "return;".to_string(),
+            Stmt::Return {
+                value: Some(value), ..
+            } => format!("return {};", self.expression(value)?),
+            Stmt::Throw { value, .. } => format!("throw {};", self.expression(value)?),
+            // A field of a class.
+            Stmt::Var {
+                name,
+                annotation,
+                initializer: None,
+                ..
+            } if self.in_class_body() => format!("{};", typed(name, annotation.as_ref())),
+            Stmt::Var {
+                name,
+                annotation,
+                initializer: None,
+                ..
+            } => format!("var {};", typed(name, annotation.as_ref())),
+            Stmt::Var {
+                name,
+                annotation,
+                initializer: Some(initializer),
+                ..
+            } => format!(
+                "var {} = {};",
+                typed(name, annotation.as_ref()),
+                self.expression(initializer)?
+            ),
+            _ => unreachable!("Not a one-line statement."),
+        })
+    }
+
+    /// Writes `header {` with the comments after it, the indented statements and `}`. Empty
+    /// blocks stay on one line.
+    fn braced(
+        &mut self,
+        header: &str,
+        comments: &[String],
+        statements: &[Stmt],
+    ) -> Result<(), Error> {
+        let open = if header.is_empty() {
+            "{".to_string()
+        } else {
+            format!("{} {{", header)
+        };
+        if statements.iter().all(is_blank) {
+            self.line(&format!("{}}}", open));
+            self.trail(comments);
+            return Ok(());
+        }
+
+        self.line(&open);
+        self.trail(comments);
+        self.depth += 1;
+        self.statements(statements)?;
+        self.depth -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    /// Writes the header and body of `if`, `else`, `while` and `for`. A simple body without
+    /// braces stays on the same line, other bodies without braces go on the next. Comments before
+    /// the body go after the header, and move a body without braces to the next line. Returns
+    /// whether the body ends with `}`.
+    fn clause(&mut self, header: &str, body: &Stmt) -> Result<bool, Error> {
+        let start = body.span().map_or(0, |span| span.start);
+        let comments = self.take_comments(|comment| comment.span.start < start);
+        match body {
+            Stmt::Block { statements, .. } if !is_for_loop(statements) => {
+                self.braced(header, &comments, statements)?;
+                Ok(true)
+            }
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::Expression { .. }
+            | Stmt::Print { .. }
+            | Stmt::Return { .. }
+            | Stmt::Throw { .. }
+                if comments.is_empty() =>
+            {
+                let body = self.simple(body)?;
+                self.line(&format!("{} {}", header, body));
+                Ok(false)
+            }
+            _ => {
+                self.line(header);
+                self.trail(&comments);
+                self.depth += 1;
+                self.statement(body)?;
+                self.depth -= 1;
+                Ok(false)
+            }
+        }
+    }
+
+    fn if_statement(
+        &mut self,
+        keyword: &str,
+        condition: &Expr,
+        then_branch: &Stmt,
+        else_branch: &Option<Stmt>,
+    ) -> Result<(), Error> {
+        let header = format!("{} ({})", keyword, self.expression(condition)?);
+        let mut braced = self.clause(&header, then_branch)?;
+        if let (Some(then_end), Some(_)) = (then_branch.span(), else_branch) {
+            // Comments before `else` stay after the then branch.
+            let else_start = self.skip(then_end.end, ";}");
+            let comments = self.take_comments(|comment| comment.span.start < else_start);
+            braced &= comments.is_empty();
+            self.trail(&comments);
+        }
+        match else_branch {
+            Some(Stmt::If {
+                condition,
+                else_branch,
+                then_branch,
+                ..
+            }) => {
+                self.join = braced;
+                self.if_statement("else if", condition, then_branch, else_branch)
+            }
+            Some(else_branch) => {
+                self.join = braced;
+                self.clause("else", else_branch).map(|_| ())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the `for` loop the parser desugared into a `while` loop.
+    fn for_loop(
+        &mut self,
+        initializer: Option<&Stmt>,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+    ) -> Result<(), Error> {
+        let initializer = match initializer {
+            Some(initializer) => self.simple(initializer)?,
+            None => ";".to_string(),
+        };
+        let condition = match condition {
+            // The parser fills in a missing condition.
+            Expr::Literal { token: None, .. } => String::new(),
+            condition => format!(" {}", self.expression(condition)?),
+        };
+        let increment = match increment {
+            Some(increment) => format!(" {}", self.expression(increment)?),
+            None => String::new(),
+        };
+        let header = format!("for ({}{};{})", initializer, condition, increment);
+        self.clause(&header, body).map(|_| ())
+    }
+
+    fn expression(&mut self, expr: &Expr) -> Result<String, Error> {
+        match expr {
+            // Literals keep how they are written, e.g. `1.50` or the escapes in a string.
+            Expr::Literal {
+                token: Some(token), ..
+            } => Ok(token.lexeme.clone()),
+            expr => expr.accept(self),
+        }
+    }
+
+    /// Joins the items with `, ` between the `open` and `close` texts. `end` is where the
+    /// closing token starts. If a comment follows an item, each item goes on its own line with
+    /// the comments after it.
+    fn items<T>(
+        &mut self,
+        (open, close): (&str, &str),
+        end: usize,
+        items: &[T],
+        span: impl Fn(&T) -> Option<Span>,
+        format: impl Fn(&mut Self, &T) -> Result<String, Error>,
+    ) -> Result<String, Error> {
+        // Where the comments after each item are, from its end to the start of the next one.
+        let spans: Vec<Option<Span>> = items.iter().map(span).collect();
+        let gaps: Vec<Option<(usize, usize)>> = spans
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let next = match spans.get(i + 1) {
+                    Some(next) => next.map(|next| next.start),
+                    None => Some(end),
+                };
+                Some((item.as_ref()?.end, next?))
+            })
+            .collect();
+        let in_gap =
+            |gap: (usize, usize), comment: &Comment| (gap.0..gap.1).contains(&comment.span.start);
+        let commented = gaps
+            .iter()
+            .flatten()
+            .any(|gap| self.comments.iter().any(|comment| in_gap(*gap, comment)));
+
+        if !commented {
+            let items = items
+                .iter()
+                .map(|item| format(self, item))
+                .collect::<Result<Vec<String>, Error>>()?;
+            return Ok(format!("{}{}{}", open, items.join(", "), close));
+        }
+
+        let mut text = open.to_string();
+        self.depth += 1;
+        for (i, (item, gap)) in items.iter().zip(gaps).enumerate() {
+            let mut line = format(self, item)?;
+            if i + 1 < items.len() {
+                line.push(',');
+            }
+            if let Some(gap) = gap {
+                let comments = self.take_comments(|comment| in_gap(gap, comment));
+                line = with_comments(&line, &comments);
+            }
+            text.push('\n');
+            text.push_str(&INDENT.repeat(self.depth));
+            text.push_str(&line);
+        }
+        self.depth -= 1;
+        text.push('\n');
+        text.push_str(&INDENT.repeat(self.depth));
+        text.push_str(close);
+        Ok(text)
+    }
+}
+
+fn is_inner_comment(statement: &Stmt) -> bool {
+    std::matches!(
+        statement,
+        Stmt::Trivia {
+            trivia: Trivia::InnerComment(_)
+        }
+    )
+}
+
+/// The text with the comments after it.
+fn with_comments(text: &str, comments: &[String]) -> String {
+    if comments.is_empty() {
+        text.to_string()
+    } else {
+        format!("{} {}", text, comments.join(" "))
+    }
+}
+
+fn is_blank(statement: &Stmt) -> bool {
+    std::matches!(
+        statement,
+        Stmt::Trivia {
+            trivia: Trivia::BlankLine
+        }
+    )
+}
+
+/// A name with its type annotation, if it has one.
+fn typed(name: &Token, annotation: Option<&Token>) -> String {
+    match annotation {
+        Some(annotation) => format!("{}: {}", name.lexeme, annotation.lexeme),
+        None => name.lexeme.clone(),
+    }
+}
+
+fn is_for_loop(statements: &[Stmt]) -> bool {
+    std::matches!(statements, [_, Stmt::While { for_loop: true, .. }])
+}
+
+impl expr::Visitor<String> for Formatter<'_> {
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _id: ExprId,
+    ) -> Result<String, Error> {
+        Ok(format!("{} = {}", name.lexeme, self.expression(value)?))
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<String, Error> {
+        Ok(format!(
+            "{} {} {}",
+            self.expression(left)?,
+            operator.lexeme,
+            self.expression(right)?
+        ))
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<String, Error> {
+        let callee = self.expression(callee)?;
+        let arguments = self.items(
+            ("(", ")"),
+            paren.span.start,
+            arguments,
+            Expr::span,
+            Self::expression,
+        )?;
+        Ok(format!("{}{}", callee, arguments))
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<String, Error> {
+        Ok(format!("{}.{}", self.expression(object)?, name.lexeme))
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<String, Error> {
+        Ok(format!("({})", self.expression(expression)?))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+    ) -> Result<String, Error> {
+        Ok(format!(
+            "{}[{}]",
+            self.expression(object)?,
+            self.expression(index)?
+        ))
+    }
+
+    fn visit_list_expr(&mut self, bracket: &Token, elements: &[Expr]) -> Result<String, Error> {
+        self.items(
+            ("[", "]"),
+            bracket.span.start,
+            elements,
+            Expr::span,
+            Self::expression,
+        )
+    }
+
+    fn visit_literal_expr(&self, value: &LiteralValue) -> Result<String, Error> {
+        Ok(match value {
+            LiteralValue::Null => "nil".to_string(),
+            LiteralValue::String(s) => format!("\"{}\"", s),
+            value => value.to_string(),
+        })
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<String, Error> {
+        self.visit_binary_expr(left, operator, right)
+    }
+
+    fn visit_map_expr(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> Result<String, Error> {
+        let span = |(key, value): &(Expr, Expr)| {
+            let (key, value) = (key.span()?, value.span()?);
+            Some(Span {
+                end: value.end,
+                ..key
+            })
+        };
+        self.items(
+            ("{", "}"),
+            brace.span.start,
+            entries,
+            span,
+            |formatter, (key, value)| {
+                Ok(format!(
+                    "{}: {}",
+                    formatter.expression(key)?,
+                    formatter.expression(value)?
+                ))
+            },
+        )
+    }
+
+    fn visit_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<String, Error> {
+        Ok(format!(
+            "{}.{} = {}",
+            self.expression(object)?,
+            name.lexeme,
+            self.expression(value)?
+        ))
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<String, Error> {
+        Ok(format!(
+            "{}[{}] = {}",
+            self.expression(object)?,
+            self.expression(index)?,
+            self.expression(value)?
+        ))
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        _keyword: &Token,
+        method: &Token,
+        _id: ExprId,
+    ) -> Result<String, Error> {
+        Ok(format!("super.{}", method.lexeme))
+    }
+
+    fn visit_this_expr(&mut self, _keyword: &Token, _id: ExprId) -> Result<String, Error> {
+        Ok("this".to_string())
+    }
+
+    fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<String, Error> {
+        Ok(format!("{}{}", operator.lexeme, self.expression(right)?))
+    }
+
+    fn visit_variable_expr(&mut self, name: &Token, _id: ExprId) -> Result<String, Error> {
+        Ok(name.lexeme.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn canonical_layout() {
+        let source = "// Counts.\nfor(var i=0;i<3;i=i+1) print i ; // Inline.\n\n\n\
+                      fun f(a,b){if(a)return b;else{return [a, {\"k\": -b}];}}\n\
+                      class A<B{\n\n  m(){ while (true) {} }\n}";
+        let formatted = format_source(source).unwrap();
+
+        assert_eq!(
+            formatted,
+            "// Counts.\n\
+             for (var i = 0; i < 3; i = i + 1) print i; // Inline.\n\
+             \n\
+             fun f(a, b) {\n  if (a) return b;\n  else {\n    return [a, {\"k\": -b}];\n  }\n}\n\
+             class A < B {\n  m() {\n    while (true) {}\n  }\n}\n"
+        );
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn comments_inside_statements_stay_after_what_they_follow() {
+        let source = "fun f(a, // first\n  b) {\n  if (a) // cond\n    print a;\n  var l = [1, // one\n    2 // two\n  ];\n}\n";
+        let formatted = "fun f(\n  a, // first\n  b\n) {\n  if (a) // cond\n    print a;\n  var l = [\n    1, // one\n    2 // two\n  ];\n}\n";
+
+        assert_eq!(format_source(source).unwrap(), formatted);
+        assert_eq!(format_source(formatted).unwrap(), formatted);
+
+        // Comments stay on the line of the branch they follow.
+        let source = "if (x) // why\n  print 1; // after\nelse\n  print 2; // else\n";
+        let formatted = "if (x) // why\n  print 1; // after\nelse print 2; // else\n";
+        assert_eq!(format_source(source).unwrap(), formatted);
+        assert_eq!(format_source(formatted).unwrap(), formatted);
+
+        let source = "if (x) {\n  print 1;\n} // then\nelse { // else\n  f(\n    1, // one\n    {\"k\": 2} // two\n  );\n}\n";
+        assert_eq!(format_source(source).unwrap(), source);
+
+        // Comments in other places go on the last line of the statement.
+        assert_eq!(
+            format_source("var a = 1 + // one\n  2;\n").unwrap(),
+            "var a = 1 + 2; // one\n"
+        );
+    }
+
+    #[test]
+    fn literals_keep_their_lexeme() {
+        let source = "print 1.50 + 0.10 + 007;\nprint \"a\\tb\";\n";
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn keeps_type_annotations() {
+        let source = "class P{x:Number;len( a : List ):Number{var n:Number=a.len;return n;}}";
+
+        assert_eq!(
+            format_source(source).unwrap(),
+            "class P {\n  x: Number;\n  len(a: List): Number {\n    var n: Number = a.len;\n    return n;\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn class_members() {
+        let source = "class C{class make(){return C();}area:Number{return 1;}set area(v){}}";
+
+        assert_eq!(
+            format_source(source).unwrap(),
+            "class C {\n  class make() {\n    return C();\n  }\n  area: Number {\n    return 1;\n  }\n  set area(v) {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn idempotent_over_examples() {
+        let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "lox") {
+                continue;
+            }
+
+            let source = fs::read_to_string(&path).unwrap();
+            let once = format_source(&source).unwrap();
+            assert_eq!(format_source(&once).unwrap(), once, "{}", path.display());
+        }
+    }
+}
@@ -1,20 +1,27 @@
 use crate::env::Environment;
 use crate::error::Error;
-use crate::interpreter::Interpreter;
+use crate::interpreter::{ControlFlow, Interpreter};
 use crate::object::Object;
 use crate::syntax::Stmt;
 use crate::token::Token;
 
 use std::cell::RefCell;
 use std::fmt;
+use std::mem;
 use std::rc::Rc;
 
+/// The implementation of a native function. It receives as many arguments as its arity.
+///
+/// Natives may capture host state and call back into Lox through the interpreter.
+pub type NativeBody = dyn Fn(&mut Interpreter, &[Object]) -> Result<Object, Error>;
+
 #[derive(Clone)]
 pub enum Function {
     // An anonymous implementation of LoxCallable in the book.
     Native {
+        name: String,
         arity: usize,
-        body: Box<fn(&Vec<Object>) -> Object>,
+        body: Rc<NativeBody>,
     },
 
     // A LoxFunction in the book.
@@ -23,22 +30,38 @@ pub enum Function {
         params: Vec<Token>,
         body: Vec<Stmt>,
         closure: Rc<RefCell<Environment>>,
+
+        /// The globals of the module the function was declared in.
+        globals: Rc<RefCell<Environment>>,
         is_initializer: bool,
     },
 }
 
 impl Function {
+    /// Calls the function. A wrong number of arguments fails with `Error::Native`, which the
+    /// call expression reports at its location.
     pub fn call(
         &self,
         interpreter: &mut Interpreter,
-        arguments: &Vec<Object>,
+        arguments: &[Object],
     ) -> Result<Object, Error> {
+        if arguments.len() != self.arity() {
+            return Err(Error::Native {
+                message: format!(
+                    "Expected {} arguments but got {}.",
+                    self.arity(),
+                    arguments.len()
+                ),
+            });
+        }
+
         match self {
-            Function::Native { body, .. } => Ok(body(arguments)),
+            Function::Native { body, .. } => body(interpreter, arguments),
             Function::User {
                 params,
                 body,
                 closure,
+                globals,
                 is_initializer,
                 ..
             } => {
@@ -48,24 +71,27 @@ impl Function {
                         .borrow_mut()
                         .define(param.lexeme.clone(), argument.clone());
                 }
-                match interpreter.execute_block(body, environment) {
-                    Err(Error::Return { value }) => {
+                // Globals are looked up in the module the function was declared in.
+                let globals = mem::replace(&mut interpreter.globals, Rc::clone(globals));
+                let result = interpreter.execute_block(body, environment);
+                interpreter.globals = globals;
+                match result? {
+                    ControlFlow::Return(value) => {
                         if *is_initializer {
                             Ok(closure
                                 .borrow()
-                                .get_at(0, "this")
+                                .get_at(0, 0)
                                 .expect("Initializer should return 'this'."))
                         } else {
                             Ok(value)
                         }
                     }
-                    Err(other) => Err(other),
                     // We don't have a return statement.
-                    Ok(..) => {
+                    _ => {
                         if *is_initializer {
                             Ok(closure
                                 .borrow()
-                                .get_at(0, "this")
+                                .get_at(0, 0)
                                 .expect("Initializer should return 'this'."))
                         } else {
                             Ok(Object::Null)
@@ -84,6 +110,7 @@ impl Function {
                 params,
                 body,
                 closure,
+                globals,
                 is_initializer,
             } => {
                 let environment = Rc::new(RefCell::new(Environment::from(closure)));
@@ -95,6 +122,7 @@ impl Function {
                     params: params.clone(),
                     body: body.clone(),
                     closure: environment,
+                    globals: Rc::clone(globals),
                     is_initializer: *is_initializer,
                 }
             }
@@ -112,7 +140,7 @@ impl Function {
 impl fmt::Debug for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Function::Native { .. } => write!(f, "<native func>"),
+            Function::Native { name, .. } => write!(f, "<native fn {}>", name),
             Function::User { name, .. } => write!(f, "<fn {}>", name.lexeme),
         }
     }
@@ -122,7 +150,7 @@ impl fmt::Debug for Function {
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Function::Native { .. } => write!(f, "<native func>"),
+            Function::Native { name, .. } => write!(f, "<native fn {}>", name),
             Function::User { name, .. } => write!(f, "<fn {}>", name.lexeme),
         }
     }
@@ -1,76 +1,156 @@
 use crate::class::{LoxClass, LoxInstance};
 use crate::env::Environment;
 use crate::error::Error;
-use crate::function::Function;
-use crate::object::Object;
+use crate::function::{Function, NativeBody};
+use crate::module::Module;
+use crate::object::{Key, LoxMap, Object};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::stdlib;
 use crate::syntax::{expr, stmt};
-use crate::syntax::{Expr, LiteralValue, Stmt};
+use crate::syntax::{Expr, ExprId, FunctionKind, LiteralValue, Stmt, Trivia};
 use crate::token::{Token, TokenType};
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How execution continues after a statement.
+///
+/// Unwinding out of a function or loop is not an error, so it is kept apart from `Error`.
+#[derive(Debug)]
+pub enum ControlFlow {
+    Normal,
+    Return(Object),
+    Break,
+    Continue,
+}
 
 pub struct Interpreter {
-    pub globals: Rc<RefCell<Environment>>,
+    pub globals: Rc<RefCell<Environment>>, // Of the module that is currently executed.
     environment: Rc<RefCell<Environment>>,
-    locals: HashMap<Token, usize>, // This might break if two Tokens are on the same line and have the same name.
+    locals: HashMap<ExprId, (usize, usize)>, // The depth and slot of each resolved local.
+
+    // Every module gets its own globals, so natives are defined again for each.
+    natives: Vec<(String, Object)>,
+
+    // The globals of each module that ran, keyed by its canonical path.
+    modules: HashMap<PathBuf, Rc<RefCell<Environment>>>,
+
+    // The files that are running, each one imported by the one before.
+    importing: Vec<PathBuf>,
+
+    // The class of the objects built-in runtime errors are caught as.
+    runtime_error: Rc<RefCell<LoxClass>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let globals = Rc::new(RefCell::new(Environment::new()));
-        let clock: Object = Object::Callable(Function::Native {
-            arity: 0,
-            body: Box::new(|_args: &Vec<Object>| {
-                Object::Number(
-                    SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("Could not retrieve time.")
-                        .as_millis() as f64,
-                )
-            }),
-        });
-        globals.borrow_mut().define("clock".to_string(), clock);
-        Interpreter {
+        let mut interpreter = Interpreter {
             globals: Rc::clone(&globals),
             environment: Rc::clone(&globals),
             locals: HashMap::new(),
-        }
+            natives: Vec::new(),
+            modules: HashMap::new(),
+            importing: Vec::new(),
+            runtime_error: Rc::new(RefCell::new(LoxClass::new("RuntimeError", None))),
+        };
+        stdlib::define(&mut interpreter);
+        interpreter
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) -> Result<(), Error> {
+    /// Defines a native function as a global. An existing global of the same name is replaced.
+    ///
+    /// `body` is only called with exactly `arity` arguments. It may fail with `Error::Native`
+    /// which is reported at the call.
+    pub fn define_native<F>(&mut self, name: &str, arity: usize, body: F)
+    where
+        F: Fn(&mut Interpreter, &[Object]) -> Result<Object, Error> + 'static,
+    {
+        let body: Rc<NativeBody> = Rc::new(body);
+        let native = Object::Callable(Function::Native {
+            name: name.to_string(),
+            arity,
+            body,
+        });
+        self.globals
+            .borrow_mut()
+            .define(name.to_string(), native.clone());
+        self.natives.retain(|(other, _)| other != name);
+        self.natives.push((name.to_string(), native));
+    }
+
+    /// Returns true if `value` is the native `name` that every module is given.
+    fn is_native(&self, name: &str, value: &Object) -> bool {
+        self.natives
+            .iter()
+            .any(|(native_name, native)| match (native, value) {
+                (
+                    Object::Callable(Function::Native { body, .. }),
+                    Object::Callable(Function::Native { body: other, .. }),
+                ) => native_name == name && Rc::ptr_eq(body, other),
+                _ => false,
+            })
+    }
+
+    /// Sets the file the interpreted code comes from and returns the previous one. Imports are
+    /// relative to its directory.
+    ///
+    /// Without a file imports are relative to the working directory.
+    pub fn set_main_file(&mut self, path: Option<&Path>) -> Option<PathBuf> {
+        let path = path.map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        mem::replace(&mut self.importing, path.into_iter().collect())
+            .into_iter()
+            .next()
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        // The resolver rejects `return` outside of functions, so anything but `Normal` cannot
+        // reach the top level.
         for statement in statements {
             self.execute(statement)?;
         }
         Ok(())
     }
 
-    fn evaluate(&mut self, expression: &Expr) -> Result<Object, Error> {
+    pub fn evaluate(&mut self, expression: &Expr) -> Result<Object, Error> {
         expression.accept(self)
     }
 
-    fn execute(&mut self, statement: &Stmt) -> Result<(), Error> {
+    fn execute(&mut self, statement: &Stmt) -> Result<ControlFlow, Error> {
         statement.accept(self)
     }
 
-    pub fn resolve(&mut self, name: &Token, depth: usize) {
-        self.locals.insert(name.clone(), depth);
+    pub fn resolve(&mut self, id: ExprId, depth: usize, slot: usize) {
+        self.locals.insert(id, (depth, slot));
     }
 
     pub fn execute_block(
         &mut self,
-        statements: &Vec<Stmt>,
+        statements: &[Stmt],
         environment: Rc<RefCell<Environment>>,
-    ) -> Result<(), Error> {
+    ) -> Result<ControlFlow, Error> {
         let previous = self.environment.clone();
-        let steps = || -> Result<(), Error> {
+        let steps = || -> Result<ControlFlow, Error> {
             self.environment = environment;
             for statement in statements {
-                self.execute(statement)?
+                match self.execute(statement)? {
+                    ControlFlow::Normal => (),
+                    unwind => return Ok(unwind),
+                }
             }
-            Ok(())
+            Ok(ControlFlow::Normal)
         };
         let result = steps();
         self.environment = previous;
@@ -80,7 +160,7 @@ impl Interpreter {
     fn is_truthy(&self, object: &Object) -> bool {
         match object {
             Object::Null => false,
-            Object::Boolean(b) => b.clone(),
+            Object::Boolean(b) => *b,
             _ => true,
         }
     }
@@ -90,17 +170,7 @@ impl Interpreter {
     }
 
     fn stringify(&self, object: Object) -> String {
-        match object {
-            Object::Boolean(b) => b.to_string(),
-            Object::Class(class) => class.borrow().name.clone(),
-            Object::Callable(f) => f.to_string(),
-            Object::Instance(instance) => {
-                format!("{} instance", instance.borrow().class.borrow().name)
-            }
-            Object::Null => "nil".to_string(),
-            Object::Number(n) => n.to_string(),
-            Object::String(s) => s,
-        }
+        object.to_string()
     }
 
     /// Equivalent to checkNumberOperands
@@ -111,9 +181,138 @@ impl Interpreter {
         })
     }
 
-    fn look_up_variable(&self, name: &Token) -> Result<Object, Error> {
-        if let Some(distance) = self.locals.get(name) {
-            self.environment.borrow().get_at(*distance, &name.lexeme)
+    /// Checks that `index` is an integer within the bounds of `list`.
+    fn list_index(&self, list: &[Object], index: &Object, bracket: &Token) -> Result<usize, Error> {
+        match index {
+            Object::Number(n) if n.fract() == 0.0 => {
+                if *n >= 0.0 && (*n as usize) < list.len() {
+                    Ok(*n as usize)
+                } else {
+                    Err(Error::Runtime {
+                        token: bracket.clone(),
+                        message: format!(
+                            "Index {} is out of range for a list of length {}.",
+                            n,
+                            list.len()
+                        ),
+                    })
+                }
+            }
+            _ => Err(Error::Runtime {
+                token: bracket.clone(),
+                message: "Index must be an integer.".to_string(),
+            }),
+        }
+    }
+
+    fn map_key(&self, object: Object, token: &Token) -> Result<Key, Error> {
+        Key::new(object).map_err(|message| Error::Runtime {
+            token: token.clone(),
+            message,
+        })
+    }
+
+    /// Runs the module at `path` once and returns its globals.
+    fn import(&mut self, keyword: &Token, path: &str) -> Result<Rc<RefCell<Environment>>, Error> {
+        let error = |message: String| Error::Runtime {
+            token: keyword.clone(),
+            message,
+        };
+
+        let directory = self
+            .importing
+            .last()
+            .and_then(|file| file.parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let file = fs::canonicalize(directory.join(path))
+            .map_err(|e| error(format!("Could not find module '{}': {}.", path, e)))?;
+
+        // The imports that lead here are added on the way back to the first module of the cycle.
+        if self.importing.contains(&file) {
+            return Err(error(format!(
+                "Import cycle: '{}' is already being imported.",
+                path
+            )));
+        }
+        if let Some(globals) = self.modules.get(&file) {
+            return Ok(Rc::clone(globals));
+        }
+
+        let source = fs::read_to_string(&file)
+            .map_err(|e| error(format!("Could not read module '{}': {}.", path, e)))?;
+        // Errors in the module point into its source until it is compiled.
+        let importer = crate::error::set_source(&file.display().to_string(), &source);
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| crate::error::parse_errors(&errors));
+        let had_error = match &statements {
+            Ok(statements) => {
+                let mut resolver = Resolver::new(self);
+                resolver.resolve_stmts(statements);
+                resolver.had_error
+            }
+            Err(_) => true,
+        };
+        crate::error::restore_source(importer);
+        let statements = match statements {
+            Ok(statements) if !had_error => statements,
+            _ => return Err(error(format!("Could not compile module '{}'.", path))),
+        };
+
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        for (name, native) in &self.natives {
+            globals.borrow_mut().define(name.clone(), native.clone());
+        }
+        let environment = mem::replace(&mut self.environment, Rc::clone(&globals));
+        let importer_globals = mem::replace(&mut self.globals, Rc::clone(&globals));
+        self.importing.push(file.clone());
+
+        let result = self.interpret(&statements);
+
+        self.importing.pop();
+        self.globals = importer_globals;
+        self.environment = environment;
+
+        // Errors keep pointing into the module. Each import they pass through is added.
+        result.map_err(|error| Error::Import {
+            keyword: keyword.clone(),
+            error: Box::new(error),
+        })?;
+
+        self.modules.insert(file, Rc::clone(&globals));
+        Ok(globals)
+    }
+
+    /// Returns the value `catch` binds for an error, or the error if it cannot be caught.
+    fn caught_value(&self, error: Error) -> Result<Object, Error> {
+        match error {
+            Error::Throw { value, .. } => Ok(*value),
+            Error::Import { keyword, error } => {
+                self.caught_value(*error).map_err(|error| Error::Import {
+                    keyword,
+                    error: Box::new(error),
+                })
+            }
+            Error::Runtime { token, message } => {
+                let object = LoxInstance::new(&self.runtime_error);
+                if let Object::Instance(ref instance) = object {
+                    let mut instance = instance.borrow_mut();
+                    let field = |name| Token::new(TokenType::Identifier, name, token.line);
+                    instance.set(&field("message"), Object::String(message));
+                    instance.set(&field("line"), Object::Number(token.line as f64));
+                }
+                Ok(object)
+            }
+            other => Err(other),
+        }
+    }
+
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> Result<Object, Error> {
+        if let Some((distance, slot)) = self.locals.get(&id) {
+            self.environment.borrow().get_at(*distance, *slot)
         } else {
             self.globals.borrow().get(name)
         }
@@ -195,49 +394,32 @@ impl expr::Visitor<Object> for Interpreter {
         &mut self,
         callee: &Expr,
         paren: &Token,
-        arguments: &Vec<Expr>,
+        arguments: &[Expr],
     ) -> Result<Object, Error> {
         let callee_value = self.evaluate(callee)?;
 
-        let argument_values: Result<Vec<Object>, Error> = arguments
-            .into_iter()
-            .map(|expr| self.evaluate(expr))
-            .collect();
+        let argument_values: Result<Vec<Object>, Error> =
+            arguments.iter().map(|expr| self.evaluate(expr)).collect();
         let args = argument_values?;
 
+        // Natives and arity mismatches fail without a location, which is the call's.
+        let at_call = |error| match error {
+            Error::Native { message } => Error::Runtime {
+                token: paren.clone(),
+                message,
+            },
+            other => other,
+        };
         match callee_value {
-            Object::Callable(function) => {
-                let args_size = args.len();
-                if args_size != function.arity() {
-                    Err(Error::Runtime {
-                        token: paren.clone(),
-                        message: format!(
-                            "Expected {} arguments but got {}.",
-                            function.arity(),
-                            args_size
-                        ),
-                    })
-                } else {
-                    function.call(self, &args)
-                }
-            }
+            Object::Callable(function) => function.call(self, &args).map_err(at_call),
             Object::Class(ref class) => {
                 // This is the call method of a class.
-                let args_size = args.len();
                 let instance = LoxInstance::new(class);
                 if let Some(initializer) = class.borrow().find_method("init") {
-                    if args_size != initializer.arity() {
-                        return Err(Error::Runtime {
-                            token: paren.clone(),
-                            message: format!(
-                                "Expected {} arguments but got {}.",
-                                initializer.arity(),
-                                args_size
-                            ),
-                        });
-                    } else {
-                        initializer.bind(instance.clone()).call(self, &args)?;
-                    }
+                    initializer
+                        .bind(instance.clone())
+                        .call(self, &args)
+                        .map_err(at_call)?;
                 }
 
                 Ok(instance)
@@ -251,13 +433,32 @@ impl expr::Visitor<Object> for Interpreter {
 
     fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<Object, Error> {
         let object = self.evaluate(object)?;
-        if let Object::Instance(ref instance) = object {
-            instance.borrow().get(name, &object)
-        } else {
-            Err(Error::Runtime {
+        if let Object::Module(ref module) = object {
+            return module.globals.borrow().get(name);
+        }
+        match object {
+            Object::Instance(ref instance) => {
+                // Getters come before fields, like setters do when assigning.
+                let getter = instance.borrow().class.borrow().find_getter(&name.lexeme);
+                match getter {
+                    Some(getter) => getter.bind(object.clone()).call(self, &[]),
+                    None => instance.borrow().get(name, &object),
+                }
+            }
+            Object::Class(ref class) => {
+                let method = class.borrow().find_class_method(&name.lexeme);
+                match method {
+                    Some(method) => Ok(Object::Callable(method.bind(object.clone()))),
+                    None => Err(Error::Runtime {
+                        token: name.clone(),
+                        message: format!("Undefined property '{}'.", name.lexeme),
+                    }),
+                }
+            }
+            _ => Err(Error::Runtime {
                 token: name.clone(),
                 message: "Only instances have properties.".to_string(),
-            })
+            }),
         }
     }
 
@@ -265,11 +466,48 @@ impl expr::Visitor<Object> for Interpreter {
         self.evaluate(expr)
     }
 
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<Object, Error> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        match object {
+            Object::List(ref list) => {
+                let list = list.borrow();
+                let i = self.list_index(&list, &index, bracket)?;
+                Ok(list[i].clone())
+            }
+            Object::Map(ref map) => {
+                let key = self.map_key(index, bracket)?;
+                map.borrow()
+                    .get(&key)
+                    .cloned()
+                    .ok_or_else(|| Error::Runtime {
+                        token: bracket.clone(),
+                        message: format!("Undefined key '{}'.", key.object()),
+                    })
+            }
+            _ => Err(Error::Runtime {
+                token: bracket.clone(),
+                message: "Only lists and maps can be indexed.".to_string(),
+            }),
+        }
+    }
+
+    fn visit_list_expr(&mut self, _bracket: &Token, elements: &[Expr]) -> Result<Object, Error> {
+        let values: Result<Vec<Object>, Error> =
+            elements.iter().map(|expr| self.evaluate(expr)).collect();
+        Ok(Object::List(Rc::new(RefCell::new(values?))))
+    }
+
     fn visit_literal_expr(&self, value: &LiteralValue) -> Result<Object, Error> {
         match value {
-            LiteralValue::Boolean(b) => Ok(Object::Boolean(b.clone())),
+            LiteralValue::Boolean(b) => Ok(Object::Boolean(*b)),
             LiteralValue::Null => Ok(Object::Null),
-            LiteralValue::Number(n) => Ok(Object::Number(n.clone())),
+            LiteralValue::Number(n) => Ok(Object::Number(*n)),
             LiteralValue::String(s) => Ok(Object::String(s.clone())),
         }
     }
@@ -286,14 +524,22 @@ impl expr::Visitor<Object> for Interpreter {
             if self.is_truthy(&l) {
                 return Ok(l);
             }
-        } else {
-            if !self.is_truthy(&l) {
-                return Ok(l);
-            }
+        } else if !self.is_truthy(&l) {
+            return Ok(l);
         }
         self.evaluate(right)
     }
 
+    fn visit_map_expr(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> Result<Object, Error> {
+        let mut map = LoxMap::new();
+        for (key, value) in entries {
+            let key = self.evaluate(key)?;
+            let key = self.map_key(key, brace)?;
+            map.insert(key, self.evaluate(value)?);
+        }
+        Ok(Object::Map(Rc::new(RefCell::new(map))))
+    }
+
     fn visit_set_expr(
         &mut self,
         object: &Expr,
@@ -304,9 +550,27 @@ impl expr::Visitor<Object> for Interpreter {
 
         if let Object::Instance(ref instance) = object {
             let value = self.evaluate(value)?;
-            instance.borrow_mut().set(property_name, value);
+            let setter = instance
+                .borrow()
+                .class
+                .borrow()
+                .find_setter(&property_name.lexeme);
+            match setter {
+                Some(setter) => {
+                    setter.bind(object.clone()).call(self, &[value])?;
+                }
+                None => instance.borrow_mut().set(property_name, value),
+            }
             let r = Object::Instance(Rc::clone(instance));
             Ok(r)
+        } else if let Object::Module(module) = object {
+            Err(Error::Runtime {
+                token: property_name.clone(),
+                message: format!(
+                    "Cannot assign to '{}' from outside module '{}'.",
+                    property_name.lexeme, module.name
+                ),
+            })
         } else {
             Err(Error::Runtime {
                 token: property_name.clone(),
@@ -315,18 +579,61 @@ impl expr::Visitor<Object> for Interpreter {
         }
     }
 
-    fn visit_super_expr(&mut self, keyword: &Token, method: &Token) -> Result<Object, Error> {
-        let distance = self
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<Object, Error> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        match object {
+            Object::List(ref list) => {
+                let value = self.evaluate(value)?;
+                let i = self.list_index(&list.borrow(), &index, bracket)?;
+                list.borrow_mut()[i] = value.clone();
+                Ok(value)
+            }
+            Object::Map(ref map) => {
+                let key = self.map_key(index, bracket)?;
+                let value = self.evaluate(value)?;
+                map.borrow_mut().insert(key, value.clone());
+                Ok(value)
+            }
+            _ => Err(Error::Runtime {
+                token: bracket.clone(),
+                message: "Only lists and maps can be indexed.".to_string(),
+            }),
+        }
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        _keyword: &Token,
+        method: &Token,
+        id: ExprId,
+    ) -> Result<Object, Error> {
+        let (distance, _) = *self
             .locals
-            .get(keyword)
+            .get(&id)
             .expect("No local distance for 'super'.");
-        let superclass = self.environment.borrow().get_at(*distance, "super")?;
+        let superclass = self.environment.borrow().get_at(distance, 0)?;
 
         // "this" is always one level nearer than "super"'s environment.
-        let instance = self.environment.borrow().get_at(*distance - 1, "this")?;
+        let instance = self.environment.borrow().get_at(distance - 1, 0)?;
 
         if let Object::Class(ref superclass) = superclass {
-            if let Some(method) = superclass.borrow().find_method(&method.lexeme) {
+            let superclass = superclass.borrow();
+            // In a class method `this` is the class.
+            let found = match instance {
+                Object::Class(_) => superclass.find_class_method(&method.lexeme),
+                _ => match superclass.find_getter(&method.lexeme) {
+                    Some(getter) => return getter.bind(instance).call(self, &[]),
+                    None => superclass.find_method(&method.lexeme),
+                },
+            };
+            if let Some(method) = found {
                 Ok(Object::Callable(method.bind(instance)))
             } else {
                 Err(Error::Runtime {
@@ -339,8 +646,8 @@ impl expr::Visitor<Object> for Interpreter {
         }
     }
 
-    fn visit_this_expr(&mut self, keyword: &Token) -> Result<Object, Error> {
-        self.look_up_variable(keyword)
+    fn visit_this_expr(&mut self, keyword: &Token, id: ExprId) -> Result<Object, Error> {
+        self.look_up_variable(keyword, id)
     }
 
     fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<Object, Error> {
@@ -348,7 +655,7 @@ impl expr::Visitor<Object> for Interpreter {
 
         match &operator.tpe {
             TokenType::Minus => match right {
-                Object::Number(n) => Ok(Object::Number(-n.clone())),
+                Object::Number(n) => Ok(Object::Number(-n)),
                 _ => self.number_operand_error(operator),
             },
             TokenType::Bang => Ok(Object::Boolean(!self.is_truthy(&right))), // TODO: is_truthy could simply return an Object.
@@ -356,45 +663,53 @@ impl expr::Visitor<Object> for Interpreter {
         }
     }
 
-    fn visit_variable_expr(&mut self, name: &Token) -> Result<Object, Error> {
-        self.look_up_variable(name)
+    fn visit_variable_expr(&mut self, name: &Token, id: ExprId) -> Result<Object, Error> {
+        self.look_up_variable(name, id)
     }
 
-    fn visit_assign_expr(&mut self, name: &Token, value: &Expr) -> Result<Object, Error> {
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        id: ExprId,
+    ) -> Result<Object, Error> {
         let v = self.evaluate(value)?;
 
-        if let Some(distance) = self.locals.get(name) {
+        if let Some((distance, slot)) = self.locals.get(&id) {
             self.environment
                 .borrow_mut()
-                .assign_at(*distance, name, v.clone())?;
+                .assign_at(*distance, *slot, v.clone())?;
         } else {
-            self.environment.borrow_mut().assign(name, v.clone())?;
+            self.globals.borrow_mut().assign(name, v.clone())?;
         }
         Ok(v)
     }
 }
 
-impl stmt::Visitor<()> for Interpreter {
-    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) -> Result<(), Error> {
+impl stmt::Visitor<ControlFlow> for Interpreter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<ControlFlow, Error> {
         self.execute_block(
             statements,
             Rc::new(RefCell::new(Environment::from(&self.environment))),
-        );
-        Ok(())
+        )
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: &Token) -> Result<ControlFlow, Error> {
+        Ok(ControlFlow::Break)
     }
 
     fn visit_class_stmt(
         &mut self,
         class_name: &Token,
         maybe_superclass: &Option<Expr>,
-        methods: &Vec<Stmt>,
-    ) -> Result<(), Error> {
+        methods: &[Stmt],
+    ) -> Result<ControlFlow, Error> {
         let superclass: Option<Rc<RefCell<LoxClass>>> = maybe_superclass
             .as_ref()
             .map(|expr| {
                 if let Object::Class(ref lox_class) = self.evaluate(expr)? {
                     Ok(Rc::clone(lox_class))
-                } else if let Expr::Variable { name } = expr {
+                } else if let Expr::Variable { name, .. } = expr {
                     Err(Error::Runtime {
                         token: name.clone(),
                         message: "Superclass must be a class.".to_string(),
@@ -407,10 +722,6 @@ impl stmt::Visitor<()> for Interpreter {
             })
             .transpose()?;
 
-        self.environment
-            .borrow_mut()
-            .define(class_name.lexeme.clone(), Object::Null);
-
         if let Some(ref class) = superclass {
             self.environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
             self.environment
@@ -418,27 +729,34 @@ impl stmt::Visitor<()> for Interpreter {
                 .define("super".to_string(), Object::Class(Rc::clone(class)));
         }
 
-        let mut class_methods: HashMap<String, Function> = HashMap::new();
+        let mut lox_class = LoxClass::new(&class_name.lexeme, superclass.clone());
         for method in methods {
-            if let Stmt::Function { name, params, body } = method {
+            if let Stmt::Function {
+                name,
+                params,
+                body,
+                kind,
+                ..
+            } = method
+            {
                 let function = Function::User {
                     name: name.clone(),
                     params: params.clone(),
                     body: body.clone(),
                     closure: Rc::clone(&self.environment),
-                    is_initializer: name.lexeme == "init",
+                    globals: Rc::clone(&self.globals),
+                    is_initializer: *kind == FunctionKind::Method && name.lexeme == "init",
                 };
-                class_methods.insert(name.lexeme.clone(), function);
-            } else {
-                unreachable!()
+                let table = match kind {
+                    FunctionKind::Getter => &mut lox_class.getters,
+                    FunctionKind::Setter => &mut lox_class.setters,
+                    FunctionKind::ClassMethod => &mut lox_class.class_methods,
+                    FunctionKind::Function | FunctionKind::Method => &mut lox_class.methods,
+                };
+                table.insert(name.lexeme.clone(), function);
             }
         }
 
-        let lox_class = LoxClass {
-            name: class_name.lexeme.clone(),
-            superclass: superclass.clone(),
-            methods: class_methods,
-        };
         let class = Object::Class(Rc::new(RefCell::new(lox_class)));
 
         // Pop environment again.
@@ -452,32 +770,45 @@ impl stmt::Visitor<()> for Interpreter {
             self.environment = parent;
         }
 
-        self.environment.borrow_mut().assign(class_name, class)?;
-        Ok(())
+        // Nothing else is defined in this environment in between, so the class still gets the slot
+        // the resolver assigned to its name.
+        self.environment
+            .borrow_mut()
+            .define(class_name.lexeme.clone(), class);
+        Ok(ControlFlow::Normal)
+    }
+
+    fn visit_continue_stmt(&mut self, _keyword: &Token) -> Result<ControlFlow, Error> {
+        Ok(ControlFlow::Continue)
+    }
+
+    fn visit_error_stmt(&mut self) -> Result<ControlFlow, Error> {
+        Ok(ControlFlow::Normal)
     }
 
-    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<(), Error> {
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<ControlFlow, Error> {
         self.evaluate(expression)?;
-        Ok(())
+        Ok(ControlFlow::Normal)
     }
 
     fn visit_function_stmt(
         &mut self,
         name: &Token,
-        params: &Vec<Token>,
-        body: &Vec<Stmt>,
-    ) -> Result<(), Error> {
+        params: &[Token],
+        body: &[Stmt],
+    ) -> Result<ControlFlow, Error> {
         let function = Function::User {
             name: name.clone(),
-            params: params.clone(),
-            body: body.clone(),
+            params: params.to_vec(),
+            body: body.to_vec(),
             closure: Rc::clone(&self.environment),
+            globals: Rc::clone(&self.globals),
             is_initializer: false,
         };
         self.environment
             .borrow_mut()
             .define(name.lexeme.clone(), Object::Callable(function));
-        Ok(())
+        Ok(ControlFlow::Normal)
     }
 
     fn visit_if_stmt(
@@ -485,36 +816,116 @@ impl stmt::Visitor<()> for Interpreter {
         condition: &Expr,
         else_branch: &Option<Stmt>,
         then_branch: &Stmt,
-    ) -> Result<(), Error> {
+    ) -> Result<ControlFlow, Error> {
         let condition_value = self.evaluate(condition)?;
         if self.is_truthy(&condition_value) {
-            self.execute(then_branch)?;
+            self.execute(then_branch)
         } else if let Some(other) = else_branch {
-            self.execute(other)?;
+            self.execute(other)
+        } else {
+            Ok(ControlFlow::Normal)
         }
+    }
 
-        Ok(())
+    fn visit_import_stmt(
+        &mut self,
+        keyword: &Token,
+        path: &str,
+        name: &Option<Token>,
+    ) -> Result<ControlFlow, Error> {
+        let globals = self.import(keyword, path)?;
+        if let Some(name) = name {
+            let module = Module {
+                name: name.lexeme.clone(),
+                globals,
+            };
+            self.environment
+                .borrow_mut()
+                .define(name.lexeme.clone(), Object::Module(Rc::new(module)));
+        } else {
+            // The values are copied, later assignments in the module are not seen here. The
+            // module's own copies of the natives would replace the importer's globals.
+            for (name, value) in globals.borrow().globals() {
+                if !self.is_native(&name, &value) {
+                    self.environment.borrow_mut().define(name, value);
+                }
+            }
+        }
+        Ok(ControlFlow::Normal)
     }
 
-    fn visit_print_stmt(&mut self, expression: &Expr) -> Result<(), Error> {
+    fn visit_print_stmt(&mut self, expression: &Expr) -> Result<ControlFlow, Error> {
         let value = self.evaluate(expression)?;
         println!("{}", self.stringify(value));
-        Ok(())
+        Ok(ControlFlow::Normal)
     }
 
-    fn visit_return_stmt(&mut self, _keyword: &Token, value: &Option<Expr>) -> Result<(), Error> {
+    fn visit_return_stmt(
+        &mut self,
+        _keyword: &Token,
+        value: &Option<Expr>,
+    ) -> Result<ControlFlow, Error> {
         let return_value: Object = value
             .as_ref()
             .map(|v| self.evaluate(v))
             .unwrap_or(Ok(Object::Null))?;
 
-        // We use Err to jump back to the top.
-        Err(Error::Return {
-            value: return_value,
+        Ok(ControlFlow::Return(return_value))
+    }
+
+    fn visit_throw_stmt(&mut self, keyword: &Token, value: &Expr) -> Result<ControlFlow, Error> {
+        let value = self.evaluate(value)?;
+        Err(Error::Throw {
+            token: keyword.clone(),
+            value: Box::new(value),
         })
     }
 
-    fn visit_var_stmt(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<(), Error> {
+    fn visit_trivia_stmt(&mut self, _trivia: &Trivia) -> Result<ControlFlow, Error> {
+        Ok(ControlFlow::Normal)
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<(Token, Vec<Stmt>)>,
+        finally: &Option<Vec<Stmt>>,
+    ) -> Result<ControlFlow, Error> {
+        let result = self.execute_block(
+            body,
+            Rc::new(RefCell::new(Environment::from(&self.environment))),
+        );
+
+        let result = match (result, catch) {
+            (Err(error), Some((name, catch_body))) => match self.caught_value(error) {
+                Ok(value) => {
+                    let environment = Rc::new(RefCell::new(Environment::from(&self.environment)));
+                    environment.borrow_mut().define(name.lexeme.clone(), value);
+                    self.execute_block(catch_body, environment)
+                }
+                Err(error) => Err(error),
+            },
+            (result, _) => result,
+        };
+
+        if let Some(finally_body) = finally {
+            // A finally that jumps or fails itself replaces the outcome of the try.
+            match self.execute_block(
+                finally_body,
+                Rc::new(RefCell::new(Environment::from(&self.environment))),
+            )? {
+                ControlFlow::Normal => (),
+                unwind => return Ok(unwind),
+            }
+        }
+        result
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+    ) -> Result<ControlFlow, Error> {
         let value: Object = initializer
             .as_ref()
             .map(|i| self.evaluate(i))
@@ -523,16 +934,306 @@ impl stmt::Visitor<()> for Interpreter {
         self.environment
             .borrow_mut()
             .define(name.lexeme.clone(), value);
-        Ok(())
+        Ok(ControlFlow::Normal)
     }
 
-    fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> Result<(), Error> {
+    fn visit_while_stmt(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+    ) -> Result<ControlFlow, Error> {
         let mut value = self.evaluate(condition)?;
         while self.is_truthy(&value) {
-            self.execute(body)?;
+            match self.execute(body)? {
+                ControlFlow::Break => break,
+                ControlFlow::Normal | ControlFlow::Continue => (),
+                ControlFlow::Return(value) => return Ok(ControlFlow::Return(value)),
+            }
+            if let Some(inc) = increment {
+                self.evaluate(inc)?;
+            }
             value = self.evaluate(condition)?
         }
 
-        Ok(())
+        Ok(ControlFlow::Normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `source` and returns the value of the global `result`.
+    fn run(source: &str) -> Object {
+        run_in(Interpreter::new(), source)
+    }
+
+    fn run_in(mut interpreter: Interpreter, source: &str) -> Object {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        let statements = Parser::new(tokens).parse().expect("Could not parse.");
+
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_stmts(&statements);
+        assert!(!resolver.had_error);
+
+        interpreter.interpret(&statements).expect("Could not run.");
+        let result = Token::new(TokenType::Identifier, "result", 1);
+        let value = interpreter.globals.borrow().get(&result).unwrap();
+        value
+    }
+
+    #[test]
+    fn shadowing_on_one_line() {
+        let value = run("var result; { var x = 1; { var x = 2; result = x + x; } }");
+        assert!(value.equals(&Object::Number(4.0)));
+
+        let value = run("var x = 1; var result; { var x = 2; result = x; } result = result + x;");
+        assert!(value.equals(&Object::Number(3.0)));
+    }
+
+    #[test]
+    fn closure_on_one_line() {
+        let value = run("var a = 1; fun f() { var a = 2; fun g() { return a; } return g; } var result = f()() + a;");
+        assert!(value.equals(&Object::Number(3.0)));
+    }
+
+    #[test]
+    fn return_unwinds_loops_and_blocks() {
+        let value = run("fun f() { var i = 0; while (true) { { i = i + 1; if (i == 3) return i; } } } var result = f();");
+        assert!(value.equals(&Object::Number(3.0)));
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let value = run("var result = 0; for (var i = 0; i < 10; i = i + 1) { if (i == 2) continue; if (i == 5) break; result = result + i; }");
+        assert!(value.equals(&Object::Number(8.0)));
+    }
+
+    #[test]
+    fn lists() {
+        let value = run("var a = [1, [2, 3]]; a[1][0] = (a[0] + 1) * 2; push(a, a[1][0]); var result = a[2] + len(a);");
+        assert!(value.equals(&Object::Number(7.0)));
+    }
+
+    #[test]
+    fn maps() {
+        let value = run("var m = {\"a\": 1, 0: 2}; m[-0] = m[0] + m[\"a\"]; m[nil] = has(m, \"b\"); var result = m[0] + len(m);");
+        assert!(value.equals(&Object::Number(6.0)));
+    }
+
+    #[test]
+    fn maps_keep_insertion_order() {
+        let value = run("var m = {\"d\": 1, \"c\": 2, \"b\": 3}; m[\"a\"] = 4; m[\"c\"] = 5; remove(m, \"d\"); m[\"d\"] = 6; var result = [keys(m), values(m), m];");
+        assert_eq!(
+            value.to_string(),
+            "[[c, b, a, d], [5, 3, 4, 6], {c: 5, b: 3, a: 4, d: 6}]"
+        );
+    }
+
+    #[test]
+    fn nan_is_not_a_map_key() {
+        let value = run("var m = {}; var result = 0; try { m[0/0] = 1; } catch (e) { result = len(m); } try { has(m, 0/0); } catch (e) { result = result + 1; }");
+        assert!(value.equals(&Object::Number(1.0)));
+    }
+
+    #[test]
+    fn print_containers_that_contain_themselves() {
+        let value = run(
+            "var result = [1]; push(result, result); var m = {}; m[\"m\"] = m; push(result, m);",
+        );
+        assert_eq!(value.to_string(), "[1, [...], {m: {...}}]");
+    }
+
+    #[test]
+    fn local_classes_use_slots() {
+        let value = run("var result; { var a = 1; class A { get() { return a; } } var b = 2; class B < A { get() { return super.get() + b; } } result = B().get(); }");
+        assert!(value.equals(&Object::Number(3.0)));
+    }
+
+    #[test]
+    fn class_methods_getters_and_setters() {
+        let value = run("class A { class make(n) { return this(n); } init(n) { this.n = n; } double { return this.n * 2; } set double(d) { this.n = d / 2; } } class B < A { class make(n) { return super.make(n + 1); } double { return super.double + 1; } } var b = B.make(1); b.double = 10; var result = b.double + b.n;");
+        assert!(value.equals(&Object::Number(16.0)));
+    }
+
+    #[test]
+    fn natives_capture_state_and_call_back() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let captured = Rc::clone(&log);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("log", 1, move |_, arguments| {
+            captured.borrow_mut().push(arguments[0].to_string());
+            Ok(Object::Null)
+        });
+        interpreter.define_native("twice", 1, |interpreter, arguments| {
+            if let Object::Callable(function) = &arguments[0] {
+                let once = function.call(interpreter, &[Object::Number(1.0)])?;
+                function.call(interpreter, &[once])
+            } else {
+                Err(Error::Native {
+                    message: "Expected a function.".to_string(),
+                })
+            }
+        });
+
+        let value = run_in(
+            interpreter,
+            "fun inc(n) { log(n); return n + 1; } var result = twice(inc);",
+        );
+        assert!(value.equals(&Object::Number(3.0)));
+        assert_eq!(*log.borrow(), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn natives_call_back_with_the_wrong_arity() {
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("call", 1, |interpreter, arguments| match &arguments[0] {
+            Object::Callable(function) => function.call(interpreter, &[]),
+            _ => Ok(Object::Null),
+        });
+
+        let value = run_in(
+            interpreter,
+            "fun f(a) { return a; } var result; try { call(f); } catch (e) { result = e.message; }",
+        );
+        assert!(value.equals(&Object::String(
+            "Expected 1 arguments but got 0.".to_string()
+        )));
+    }
+
+    #[test]
+    fn modules_run_once() {
+        let directory = std::env::temp_dir().join(format!("lox-modules-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("counter.lox"),
+            "var count = 0; fun next() { count = count + 1; return count; }",
+        )
+        .unwrap();
+
+        let source = format!(
+            "import \"{0}\" as a; import \"{0}\" as b; a.next(); var result = b.next();",
+            directory.join("counter.lox").display()
+        );
+        let value = run(&source);
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(value.equals(&Object::Number(2.0)));
+    }
+
+    #[test]
+    fn imports_keep_the_importers_globals_and_members_are_read_only() {
+        let directory =
+            std::env::temp_dir().join(format!("lox-module-globals-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("m.lox"),
+            "var a = 1; fun keys(m) { return 2; }",
+        )
+        .unwrap();
+
+        let source = format!(
+            "fun len(x) {{ return 3; }} import \"{}\" as m; import \"{0}\"; var result = [len(\"abc\"), a, keys({{}})]; try {{ m.a = 2; }} catch (e) {{ push(result, e.message); }}",
+            directory.join("m.lox").display()
+        );
+        let value = run(&source);
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[3, 1, 2, Cannot assign to 'a' from outside module 'm'.]"
+        );
+    }
+
+    #[test]
+    fn errors_in_module_functions_point_into_the_module() {
+        let directory =
+            std::env::temp_dir().join(format!("lox-module-errors-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let module = "// bad\nfun boom() { return 1 + nil; }\n";
+        fs::write(directory.join("bad.lox"), module).unwrap();
+
+        let source = format!(
+            "import \"{}\";\n\nboom();",
+            directory.join("bad.lox").display()
+        );
+        crate::error::set_source("main.lox", &source);
+        let mut scanner = Scanner::new(source.clone());
+        let statements = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve_stmts(&statements);
+        let error = interpreter.interpret(&statements);
+        fs::remove_dir_all(&directory).unwrap();
+
+        match error {
+            Err(Error::Runtime { token, .. }) => {
+                assert_eq!(token.span.source, crate::token::SourceId(1));
+                assert_eq!(&module[token.span.start..token.span.end], "+");
+                assert_eq!((token.line, token.column), (2, 23));
+            }
+            other => panic!("Expected a runtime error, got {:?}.", other),
+        }
+        assert_eq!(crate::error::current_source(), crate::token::SourceId(0));
+    }
+
+    #[test]
+    fn module_errors_keep_their_location_and_imports() {
+        let directory =
+            std::env::temp_dir().join(format!("lox-module-imports-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("bad.lox"), "var x = 1;\nprint x + nil;\n").unwrap();
+        fs::write(directory.join("mid.lox"), "import \"bad.lox\";\n").unwrap();
+        fs::write(directory.join("a.lox"), "import \"b.lox\";\n").unwrap();
+        fs::write(directory.join("b.lox"), "\nimport \"a.lox\";\n").unwrap();
+
+        let run_main = |module: &str| {
+            let source = format!("import \"{}\";", directory.join(module).display());
+            let mut scanner = Scanner::new(source);
+            let statements = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            let mut interpreter = Interpreter::new();
+            Resolver::new(&mut interpreter).resolve_stmts(&statements);
+            interpreter.interpret(&statements).unwrap_err()
+        };
+        let nested = run_main("mid.lox");
+        let cycle = run_main("a.lox");
+        fs::remove_dir_all(&directory).unwrap();
+
+        // Each import wraps the error once, outermost first.
+        let unwrap = |error: Error, lines: &[i32]| {
+            let mut error = error;
+            for line in lines {
+                match error {
+                    Error::Import {
+                        keyword,
+                        error: inner,
+                    } => {
+                        assert_eq!(keyword.line, *line);
+                        error = *inner;
+                    }
+                    other => panic!("Expected an import error, got {:?}.", other),
+                }
+            }
+            match error {
+                Error::Runtime { token, message } => (token.line, message),
+                other => panic!("Expected a runtime error, got {:?}.", other),
+            }
+        };
+        assert_eq!(
+            unwrap(nested, &[1, 1]),
+            (
+                2,
+                "Operands must be two numbers or two strings.".to_string()
+            )
+        );
+        let (line, message) = unwrap(cycle, &[1, 1]);
+        assert_eq!(line, 2);
+        assert_eq!(message, "Import cycle: 'a.lox' is already being imported.");
+    }
+
+    #[test]
+    fn catch_thrown_values_and_runtime_errors() {
+        let value = run("var result = 0; fun f() { try { throw 1; } finally { result = result + 10; } } try { f(); } catch (e) { result = result + e; } try { nil(); } catch (e) { result = result + e.line; }");
+        assert!(value.equals(&Object::Number(12.0)));
     }
 }
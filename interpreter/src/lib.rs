@@ -0,0 +1,18 @@
+//! The tree-walk interpreter from part II of the book. The `interpreter` binary runs it, and the
+//! language server uses its scanner, parser and resolver.
+
+pub mod class;
+pub mod env;
+pub mod error;
+pub mod formatter;
+pub mod function;
+pub mod interpreter;
+pub mod module;
+pub mod object;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod stdlib;
+pub mod syntax;
+pub mod token;
+pub mod typecheck;
@@ -1,89 +1,688 @@
-mod class;
-mod env;
-mod error;
-mod function;
-mod interpreter;
-mod object;
-mod parser;
-mod resolver;
-mod scanner;
-mod syntax;
-mod token;
-
 use std::fs;
-use std::io::{self, BufRead};
+use std::io::{self, IsTerminal, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
-use error::Error;
-use interpreter::Interpreter;
-use parser::Parser;
-use resolver::Resolver;
-use scanner::Scanner;
-use syntax::AstPrinter;
+use diagnostics::Format;
+use interpreter::error::{self, Error};
+use interpreter::formatter;
+use interpreter::function::Function;
+use interpreter::interpreter::Interpreter;
+use interpreter::object::Object;
+use interpreter::parser::Parser;
+use interpreter::resolver::{Lint, Resolver};
+use interpreter::scanner::Scanner;
+use interpreter::stdlib;
+use interpreter::syntax::{AstPrinter, Stmt};
+use interpreter::token::{Token, TokenType};
+use interpreter::typecheck::TypeChecker;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+const USAGE: &str = "\
+Usage: lox-rs [options] [command] [script | -] [arguments...]
+
+Commands:
+  run       Run the script. This is the default when a script is given.
+  repl      Start the REPL. This is the default without a script.
+  check     Parse and resolve the script without running it.
+  lint      Like check, but also warn about code that is probably wrong.
+  typecheck Like check, but also check the type annotations.
+  tokens    Print the tokens of the script.
+  ast       Print the syntax tree of the script.
+  fmt       Format the script in place, or print it if it is not a file.
+
+Options:
+  -e <code>                    Use the code instead of a script.
+  --check                      With fmt, only list the script if it is not formatted.
+  --allow=<lint>,...           With lint, do not warn about these. The lints are argument-count,
+                               shadowing, undeclared-assignment, unreachable-code,
+                               unused-parameter and unused-variable.
+  --error-format=human|json    How errors are printed.
+  -h, --help                   Show this help.
+
+The script is read from stdin if it is '-'. The arguments after it are returned by args().";
+
+const HELP: &str = "\
+Enter statements, or expressions to print their value. Unclosed braces continue on the next line.
+  :help          Show this help.
+  :load <file>   Run a file in this session.
+  :env           List the global variables.
+  :ast <code>    Show the syntax tree of the code.
+  :quit          Leave the REPL. Ctrl-D works too.";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Input {
+    File(String),
+    Stdin,
+    Inline(String),
+}
+
+impl Input {
+    /// Returns the name errors are reported with and the source.
+    fn read(&self) -> Result<(String, String), Error> {
+        match self {
+            Input::File(path) => match fs::read_to_string(path) {
+                Ok(source) => Ok((path.clone(), source)),
+                Err(e) => Err(Error::Io(io::Error::new(
+                    e.kind(),
+                    format!("Could not read '{}': {}.", path, e),
+                ))),
+            },
+            Input::Stdin => Ok(("<stdin>".to_string(), io::read_to_string(io::stdin())?)),
+            Input::Inline(code) => Ok(("<-e>".to_string(), code.clone())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Help,
+    Repl,
+    Run(Input),
+    Check(Input),
+    Lint { input: Input, allow: Vec<Lint> },
+    Typecheck(Input),
+    Tokens(Input),
+    Ast(Input),
+    Fmt { input: Input, check: bool },
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    format: Format,
+
+    /// The arguments after the script, for `args()`.
+    arguments: Vec<String>,
+}
+
+/// Parses the command line without the program name.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut name: Option<&str> = None;
+    let mut input: Option<Input> = None;
+    let mut format = Format::Human;
+    let mut check = false;
+    let mut allow: Option<Vec<Lint>> = None;
+
+    let mut args = args.iter();
+    while input.is_none() {
+        let arg = match args.next() {
+            Some(arg) => arg.as_str(),
+            None => break,
+        };
+        match arg {
+            "-h" | "--help" => name = Some("help"),
+            "-e" => {
+                let code = args.next().ok_or("Missing code after '-e'.")?;
+                input = Some(Input::Inline(code.clone()));
+            }
+            "-" => input = Some(Input::Stdin),
+            "--check" => check = true,
+            "run" | "repl" | "check" | "lint" | "typecheck" | "tokens" | "ast" | "fmt"
+                if name.is_none() =>
+            {
+                name = Some(arg)
+            }
+            _ if arg.starts_with("--allow=") => {
+                let lints = arg["--allow=".len()..].split(',').map(str::parse);
+                allow
+                    .get_or_insert_with(Vec::new)
+                    .extend(lints.collect::<Result<Vec<Lint>, String>>()?);
+            }
+            _ => match arg.strip_prefix("--error-format=") {
+                Some(value) => format = value.parse()?,
+                None if arg.starts_with('-') => return Err(format!("Unknown option '{}'.", arg)),
+                None => input = Some(Input::File(arg.to_string())),
+            },
+        }
+    }
+
+    let default = if input.is_some() { "run" } else { "repl" };
+    let name = name.unwrap_or(default);
+    if check && name != "fmt" {
+        return Err("'--check' only works with fmt.".to_string());
+    }
+    if allow.is_some() && name != "lint" {
+        return Err("'--allow' only works with lint.".to_string());
+    }
+    let command = match (name, input) {
+        ("help", _) => Command::Help,
+        ("repl", None) => Command::Repl,
+        ("repl", Some(_)) => return Err("The REPL does not take a script.".to_string()),
+        (_, None) => return Err("Missing script.".to_string()),
+        ("run", Some(input)) => Command::Run(input),
+        ("check", Some(input)) => Command::Check(input),
+        ("lint", Some(input)) => Command::Lint {
+            input,
+            allow: allow.unwrap_or_default(),
+        },
+        ("typecheck", Some(input)) => Command::Typecheck(input),
+        ("tokens", Some(input)) => Command::Tokens(input),
+        ("ast", Some(input)) => Command::Ast(input),
+        ("fmt", Some(input)) => Command::Fmt { input, check },
+        _ => unreachable!(),
+    };
+
+    Ok(Options {
+        command,
+        format,
+        arguments: args.cloned().collect(),
+    })
+}
 
 struct Lox {
     interpreter: Interpreter,
+
+    /// The lints the resolver warns about. None unless the command is `lint`.
+    lints: Vec<Lint>,
 }
 
 impl Lox {
     fn new() -> Self {
         Lox {
             interpreter: Interpreter::new(),
+            lints: Vec::new(),
         }
     }
 
+    /// Runs a file in the current session. Imports in the file are relative to it, later entries
+    /// import relative to the working directory again.
     fn run_file(&mut self, path: &str) -> Result<(), Error> {
         let source = fs::read_to_string(path)?;
-        self.run(source)
+        let previous = self.interpreter.set_main_file(Some(Path::new(path)));
+        let result = self.run(path, source);
+        self.interpreter.set_main_file(previous.as_deref());
+        result
     }
 
+    /// Reads entries until the input ends and runs them in the same interpreter.
+    ///
+    /// At a terminal lines can be edited, and entries of this and earlier sessions are recalled
+    /// with the arrow keys from the history file. Piped input is read as is and not recorded.
     fn run_prompt(&mut self) -> Result<(), Error> {
-        let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            self.run(line?); // Ignore error.
-            print!("> ");
+        let mut lines = Lines::new();
+        let mut source = String::new();
+
+        loop {
+            let line = match lines.read(if source.is_empty() { "> " } else { ". " })? {
+                Line::Text(line) => line,
+                Line::Interrupted => {
+                    source.clear();
+                    continue;
+                }
+                Line::End => break,
+            };
+
+            source.push_str(&line);
+            if !is_complete(&source) {
+                continue;
+            }
+
+            let entry = mem::take(&mut source);
+            lines.add_history(&entry);
+
+            match entry.trim().strip_prefix(':') {
+                Some("quit") => break,
+                Some(command) => self.command(command),
+                None if entry.trim().is_empty() => (),
+                None => report(self.run_entry(entry)),
+            }
+        }
+        lines.save_history();
+
+        // Report errors in an unfinished entry.
+        if !source.is_empty() {
+            report(self.run_entry(source));
         }
+
         Ok(())
     }
 
-    fn run(&mut self, source: String) -> Result<(), Error> {
+    fn command(&mut self, command: &str) {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+
+        match name {
+            "help" => println!("{}", HELP),
+            "load" if !argument.is_empty() => match self.run_file(argument) {
+                Err(Error::Io(e)) => eprintln!("Could not read '{}': {}.", argument, e),
+                result => report(result),
+            },
+            "env" => {
+                let mut globals = self.interpreter.globals.borrow().globals();
+                globals.retain(|(_, value)| {
+                    !matches!(value, Object::Callable(Function::Native { .. }))
+                });
+                globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+                for (name, value) in globals {
+                    println!("{} = {}", name, value);
+                }
+            }
+            "ast" if !argument.is_empty() => {
+                error::set_source("<stdin>", argument);
+                let mut scanner = Scanner::new(argument.to_string());
+                let tokens = scanner.scan_tokens();
+                let statements = match bare_expression(tokens) {
+                    Some(statement) => Ok(vec![statement]),
+                    None => Parser::new(tokens).parse(),
+                };
+                match statements {
+                    Ok(statements) => print_statements(&statements),
+                    Err(errors) => {
+                        error::parse_errors(&errors);
+                    }
+                }
+            }
+            _ => eprintln!("Unknown command ':{}'. Enter :help for a list.", command),
+        }
+    }
+
+    /// Runs a command that works on a script.
+    fn execute(&mut self, command: &Command) -> Result<(), Error> {
+        match command {
+            Command::Run(input) => {
+                let (file, source) = input.read()?;
+                if let Input::File(path) = input {
+                    self.interpreter.set_main_file(Some(Path::new(path)));
+                }
+                self.run(&file, source)
+            }
+            Command::Lint { input, allow } => {
+                self.lints = Lint::ALL
+                    .iter()
+                    .filter(|lint| !allow.contains(lint))
+                    .copied()
+                    .collect();
+                self.execute(&Command::Check(input.clone()))
+            }
+            Command::Check(input) => {
+                let (file, source) = input.read()?;
+                self.compile(&file, source).map(|_| ())
+            }
+            Command::Typecheck(input) => {
+                let (file, source) = input.read()?;
+                let statements = self.compile(&file, source)?;
+                let mut checker = TypeChecker::new();
+                checker.check_stmts(&statements);
+                if checker.had_error {
+                    Err(Error::Parse)
+                } else {
+                    Ok(())
+                }
+            }
+            Command::Tokens(input) => {
+                let (file, source) = input.read()?;
+                error::set_source(&file, &source);
+                let mut scanner = Scanner::new(source);
+                for token in scanner.scan_tokens() {
+                    println!("{:>4}:{:<3} {}", token.line, token.column, token);
+                }
+                if scanner.had_error {
+                    Err(Error::Parse)
+                } else {
+                    Ok(())
+                }
+            }
+            Command::Ast(input) => {
+                let (file, source) = input.read()?;
+                error::set_source(&file, &source);
+                let mut scanner = Scanner::new(source);
+                let statements = Parser::new(scanner.scan_tokens())
+                    .parse()
+                    .map_err(|errors| error::parse_errors(&errors))?;
+                print_statements(&statements);
+                Ok(())
+            }
+            Command::Fmt { input, check: _ } => {
+                let (file, source) = input.read()?;
+                error::set_source(&file, &source);
+                let formatted = formatter::format_source(&source)?;
+                match input {
+                    Input::File(path) if formatted != source => fs::write(path, formatted)?,
+                    Input::File(_) => (),
+                    Input::Stdin | Input::Inline(_) => print!("{}", formatted),
+                }
+                Ok(())
+            }
+            Command::Help | Command::Repl => unreachable!(),
+        }
+    }
+
+    fn run(&mut self, file: &str, source: String) -> Result<(), Error> {
+        let statements = self.compile(file, source)?;
+        self.interpreter.interpret(&statements)
+    }
+
+    /// Scans, parses and resolves the source. Errors are reported already, so they are all
+    /// `Error::Parse`.
+    fn compile(&mut self, file: &str, source: String) -> Result<Vec<Stmt>, Error> {
+        error::set_source(file, &source);
         let mut scanner = Scanner::new(source);
         let tokens = scanner.scan_tokens();
 
-        let mut parser = Parser::new(tokens);
-        let statements = parser.parse()?;
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| error::parse_errors(&errors))?;
+        self.resolve(&statements)?;
+        if scanner.had_error {
+            return Err(Error::Parse);
+        }
+        Ok(statements)
+    }
 
-        let mut resolver = Resolver::new(&mut self.interpreter);
-        resolver.resolve_stmts(&statements);
+    /// Runs an entry of the REPL. The value of a bare expression like `1 + 2` is printed.
+    fn run_entry(&mut self, source: String) -> Result<(), Error> {
+        error::set_source("<stdin>", &source);
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+
+        let bare = bare_expression(tokens);
+        let is_bare = bare.is_some();
+        let statements = match bare {
+            Some(statement) => vec![statement],
+            None => Parser::new(tokens)
+                .parse()
+                .map_err(|errors| error::parse_errors(&errors))?,
+        };
+        self.resolve(&statements)?;
+        if scanner.had_error {
+            return Err(Error::Parse);
+        }
+
+        match statements.as_slice() {
+            [Stmt::Expression { expression }] if is_bare => {
+                let value = self.interpreter.evaluate(expression)?;
+                if !matches!(value, Object::Null) {
+                    println!("{}", value);
+                }
+                Ok(())
+            }
+            _ => self.interpreter.interpret(&statements),
+        }
+    }
 
+    /// Resolves the statements. Errors are reported already, so they are all `Error::Parse`.
+    fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Error> {
+        let mut resolver = Resolver::with_lints(&mut self.interpreter, &self.lints);
+        resolver.resolve_stmts(statements);
         if resolver.had_error {
-            return Ok(());
+            Err(Error::Parse)
+        } else {
+            Ok(())
         }
+    }
+}
 
-        self.interpreter.interpret(&statements)?;
-        Ok(())
+fn print_statements(statements: &[Stmt]) {
+    match AstPrinter.print_statements(statements) {
+        Ok(tree) => println!("{}", tree),
+        Err(e) => error::runtime_error(&e),
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
-    let args: Vec<String> = std::env::args().collect();
-    let mut lox = Lox::new();
-    match args.as_slice() {
-        [_, file] => match lox.run_file(file) {
-            Ok(_) => (),
-            Err(Error::Return { .. }) => unreachable!(),
-            Err(Error::Runtime { message, .. }) => {
-                eprintln!("Error: {}", message);
-                exit(70)
-            }
-            Err(Error::Parse) => exit(65),
-            Err(Error::Io(_)) => unimplemented!(),
+/// Reports an error that ended a command or REPL entry. Syntax errors are reported while parsing.
+fn report(result: Result<(), Error>) {
+    match result {
+        Ok(_) | Err(Error::Parse) => (),
+        Err(e) => error::runtime_error(&e),
+    }
+}
+
+/// Returns the expression statement if the tokens are a single expression without its `;`.
+fn bare_expression(tokens: &[Token]) -> Option<Stmt> {
+    let (eof, tokens) = tokens.split_last()?;
+    if tokens.last().is_none_or(|t| t.tpe == TokenType::Semicolon) {
+        return None;
+    }
+
+    let mut tokens = tokens.to_vec();
+    tokens.push(Token::new(TokenType::Semicolon, ";", eof.line));
+    tokens.push(eof.clone());
+    match Parser::new(&tokens).parse() {
+        Ok(mut statements) if statements.len() == 1 => match statements.pop() {
+            Some(statement @ Stmt::Expression { .. }) => Some(statement),
+            _ => None,
         },
-        [_] => lox.run_prompt()?,
-        _ => {
-            eprintln!("Usage: lox-rs [script]");
-            exit(64)
+        _ => None,
+    }
+}
+
+/// Returns false if the source has unclosed parentheses, braces, brackets or strings.
+fn is_complete(source: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' => depth -= 1,
+            // The guard skips to the closing quote.
+            '"' if !chars.any(|c| c == '"') => return false,
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|&c| c != '\n').is_some() {},
+            _ => (),
+        }
+    }
+    depth <= 0
+}
+
+/// A line read by the REPL.
+enum Line {
+    /// The line with its newline.
+    Text(String),
+
+    /// Ctrl-C was pressed, which drops the unfinished entry.
+    Interrupted,
+
+    /// The input ended.
+    End,
+}
+
+/// Where the REPL reads its lines from.
+enum Lines {
+    /// A terminal, with line editing and the history file it was loaded from.
+    Editor(Box<DefaultEditor>, Option<PathBuf>),
+
+    /// Piped input.
+    Plain(io::Stdin),
+}
+
+impl Lines {
+    fn new() -> Self {
+        let stdin = io::stdin();
+        if !stdin.is_terminal() {
+            return Lines::Plain(stdin);
+        }
+        match DefaultEditor::new() {
+            Ok(mut editor) => {
+                let history = history_file();
+                if let Some(path) = &history {
+                    // There is no history on the first run.
+                    let _ = editor.load_history(path);
+                }
+                Lines::Editor(Box::new(editor), history)
+            }
+            Err(_) => Lines::Plain(stdin),
+        }
+    }
+
+    fn read(&mut self, prompt: &str) -> Result<Line, Error> {
+        match self {
+            Lines::Editor(editor, _) => match editor.readline(prompt) {
+                Ok(line) => Ok(Line::Text(line + "\n")),
+                Err(ReadlineError::Interrupted) => Ok(Line::Interrupted),
+                Err(ReadlineError::Eof) => Ok(Line::End),
+                Err(ReadlineError::Io(e)) => Err(Error::Io(e)),
+                Err(e) => Err(Error::Io(io::Error::other(e))),
+            },
+            Lines::Plain(stdin) => {
+                print!("{}", prompt);
+                io::stdout().flush()?;
+
+                let mut line = String::new();
+                if stdin.read_line(&mut line)? == 0 {
+                    println!();
+                    Ok(Line::End)
+                } else {
+                    Ok(Line::Text(line))
+                }
+            }
+        }
+    }
+
+    /// Makes a complete entry recallable.
+    fn add_history(&mut self, entry: &str) {
+        if let Lines::Editor(editor, _) = self {
+            if !entry.trim().is_empty() {
+                let _ = editor.add_history_entry(entry.trim_end());
+            }
+        }
+    }
+
+    /// Appends the entries of this session to the history file.
+    fn save_history(&mut self) {
+        if let Lines::Editor(editor, Some(path)) = self {
+            if let Err(e) = editor.append_history(path) {
+                eprintln!("Could not save the history to '{}': {}.", path.display(), e);
+            }
+        }
+    }
+}
+
+/// `$LOX_HISTORY`, or `.lox_history` in the home directory.
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("LOX_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".lox_history")))
+}
+
+/// Prints the name of the script if formatting would change it.
+fn is_formatted(input: &Input) -> Result<bool, Error> {
+    let (file, source) = input.read()?;
+    error::set_source(&file, &source);
+    let formatted = formatter::format_source(&source)? == source;
+    if !formatted {
+        println!("{}", file);
+    }
+    Ok(formatted)
+}
+
+/// The exit code of an error, following sysexits.h.
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Parse => 65, // EX_DATAERR
+        Error::Io(_) => 74, // EX_IOERR
+        _ => 70,            // EX_SOFTWARE
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|message| {
+        eprintln!("{}\n\n{}", message, USAGE);
+        exit(64) // EX_USAGE
+    });
+    error::set_format(options.format);
+
+    let mut lox = Lox::new();
+    stdlib::define_args(&mut lox.interpreter, &options.arguments);
+    let result = match &options.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
         }
+        Command::Repl => lox.run_prompt(),
+        Command::Fmt { input, check: true } => match is_formatted(input) {
+            Ok(false) => exit(1),
+            result => result.map(|_| ()),
+        },
+        command => lox.execute(command),
+    };
+
+    if let Err(e) = result {
+        let code = exit_code(&e);
+        report(Err(e));
+        exit(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line() {
+        let parse = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            parse_args(&args)
+        };
+
+        assert_eq!(parse(&[]).unwrap().command, Command::Repl);
+        assert_eq!(
+            parse(&["--error-format=json", "a.lox", "x", "-e"]),
+            Ok(Options {
+                command: Command::Run(Input::File("a.lox".to_string())),
+                format: Format::Json,
+                arguments: vec!["x".to_string(), "-e".to_string()],
+            })
+        );
+        assert_eq!(
+            parse(&["check", "-"]).unwrap().command,
+            Command::Check(Input::Stdin)
+        );
+        assert_eq!(
+            parse(&["ast", "-e", "1;"]).unwrap().command,
+            Command::Ast(Input::Inline("1;".to_string()))
+        );
+        assert_eq!(
+            parse(&["fmt", "--check", "a.lox"]).unwrap().command,
+            Command::Fmt {
+                input: Input::File("a.lox".to_string()),
+                check: true
+            }
+        );
+        assert!(parse(&["--check", "a.lox"]).is_err());
+        assert_eq!(
+            parse(&["lint", "--allow=shadowing,unused-parameter", "a.lox"])
+                .unwrap()
+                .command,
+            Command::Lint {
+                input: Input::File("a.lox".to_string()),
+                allow: vec![Lint::Shadowing, Lint::UnusedParameter]
+            }
+        );
+        assert!(parse(&["lint", "--allow=bogus", "a.lox"]).is_err());
+        assert_eq!(
+            parse(&["typecheck", "-"]).unwrap().command,
+            Command::Typecheck(Input::Stdin)
+        );
+        assert!(parse(&["tokens"]).is_err());
+        assert!(parse(&["repl", "a.lox"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn complete_entries() {
+        assert!(is_complete("print 1;\n"));
+        assert!(is_complete("print \"{\"; // (\n"));
+        assert!(!is_complete("fun f() {\n"));
+        assert!(!is_complete("var l = [1,\n"));
+        assert!(!is_complete("print \"a\n"));
+    }
+
+    #[test]
+    fn bare_expressions() {
+        let bare = |source: &str| {
+            let mut scanner = Scanner::new(source.to_string());
+            bare_expression(scanner.scan_tokens()).is_some()
+        };
+
+        assert!(bare("1 + 2"));
+        assert!(bare("f(1)"));
+        assert!(!bare("1 + 2;"));
+        assert!(!bare("var a = 1"));
+        assert!(!bare("{ 1 }"));
+        assert!(!bare(""));
     }
-    Ok(())
 }
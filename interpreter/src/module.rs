@@ -0,0 +1,18 @@
+use crate::env::Environment;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// The namespace `import "path" as name;` binds to `name`.
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub globals: Rc<RefCell<Environment>>,
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
@@ -1,7 +1,12 @@
 use crate::class::{LoxClass, LoxInstance};
 use crate::function::Function;
+use crate::module::Module;
 
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::rc::Rc;
 
 /// A simple representation of an Lox object akin to a Java `Object`.
@@ -11,6 +16,9 @@ pub enum Object {
     Class(Rc<RefCell<LoxClass>>),
     Callable(Function),
     Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Object>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    Module(Rc<Module>),
     Null,
     Number(f64),
     String(String),
@@ -25,7 +33,192 @@ impl Object {
             (Object::Boolean(left), Object::Boolean(right)) => left == right,
             (Object::Number(left), Object::Number(right)) => left == right,
             (Object::String(left), Object::String(right)) => left.eq(right),
+            (Object::List(left), Object::List(right)) => Rc::ptr_eq(left, right),
+            (Object::Map(left), Object::Map(right)) => Rc::ptr_eq(left, right),
+            (Object::Module(left), Object::Module(right)) => Rc::ptr_eq(left, right),
             _ => false, // TODO: this should be defined or all.
         }
     }
 }
+
+/// An `Object` that can be used as a key of a map.
+///
+/// Only strings, numbers other than NaN, booleans and nil are hashable. Two keys are equal if their objects are
+/// `equals`.
+#[derive(Debug, Clone)]
+pub enum Key {
+    Boolean(bool),
+    Null,
+    Number(f64),
+    String(String),
+}
+
+impl Key {
+    pub fn new(object: Object) -> Result<Self, String> {
+        match object {
+            Object::Boolean(b) => Ok(Key::Boolean(b)),
+            Object::Null => Ok(Key::Null),
+            // NaN is not equal to itself, so it could never be found again.
+            Object::Number(n) if !n.is_nan() => Ok(Key::Number(n)),
+            Object::String(s) => Ok(Key::String(s)),
+            _ => Err(
+                "Map keys must be strings, numbers other than NaN, booleans or nil.".to_string(),
+            ),
+        }
+    }
+
+    pub fn object(&self) -> Object {
+        match self {
+            Key::Boolean(b) => Object::Boolean(*b),
+            Key::Null => Object::Null,
+            Key::Number(n) => Object::Number(*n),
+            Key::String(s) => Object::String(s.clone()),
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.object().equals(&other.object())
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            Key::Boolean(b) => b.hash(state),
+            Key::Null => (),
+            // 0.0 and -0.0 are equal so they must hash the same.
+            Key::Number(n) if *n == 0.0 => 0.0f64.to_bits().hash(state),
+            Key::Number(n) => n.to_bits().hash(state),
+            Key::String(s) => s.hash(state),
+        }
+    }
+}
+
+/// The entries of a map in the order their keys were first inserted.
+///
+/// Keys, values and printed maps come out in this order, so scripts print the same every run.
+#[derive(Debug, Clone, Default)]
+pub struct LoxMap {
+    entries: Vec<(Key, Object)>,
+
+    /// The position of each key in `entries`.
+    index: HashMap<Key, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Object> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Replacing the value of a key keeps its position.
+    pub fn insert(&mut self, key: Key, value: Object) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &Key) -> Option<Object> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            *self.index.get_mut(key).expect("Entries should be indexed.") -= 1;
+        }
+        Some(value)
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Object)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+/// This is `stringify` from the book.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, &mut Vec::new())
+    }
+}
+
+impl Object {
+    /// Writes the object. `printing` holds the lists and maps that are being written, so a
+    /// container that contains itself is written as `[...]` or `{...}` instead of forever.
+    fn write(&self, f: &mut fmt::Formatter<'_>, printing: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            Object::Boolean(b) => write!(f, "{}", b),
+            Object::Class(class) => write!(f, "{}", class.borrow().name),
+            Object::Callable(function) => write!(f, "{}", function),
+            Object::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Object::List(list) => {
+                let pointer = Rc::as_ptr(list) as *const ();
+                if printing.contains(&pointer) {
+                    return write!(f, "[...]");
+                }
+                printing.push(pointer);
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write(f, printing)?;
+                }
+                printing.pop();
+                write!(f, "]")
+            }
+            Object::Map(map) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                if printing.contains(&pointer) {
+                    return write!(f, "{{...}}");
+                }
+                printing.push(pointer);
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key.object())?;
+                    value.write(f, printing)?;
+                }
+                printing.pop();
+                write!(f, "}}")
+            }
+            Object::Module(module) => write!(f, "{}", module),
+            Object::Null => write!(f, "nil"),
+            Object::Number(n) => write!(f, "{}", n),
+            Object::String(s) => write!(f, "{}", s),
+        }
+    }
+}
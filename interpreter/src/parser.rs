@@ -1,10 +1,26 @@
-use crate::error::{parser_error, Error};
-use crate::syntax::{Expr, LiteralValue, Stmt};
-use crate::token::{Token, TokenType};
+use crate::error::{Error, ParseError};
+use crate::syntax::{Expr, ExprId, FunctionKind, LiteralValue, Signature, Stmt, Trivia};
+use crate::token::{Comment, Token, TokenType};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Ids are unique across parsers since the REPL parses each line with a new `Parser` but keeps
+// the same `Interpreter`.
+static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
 
 pub struct Parser<'t> {
     tokens: &'t Vec<Token>,
     current: usize,
+    errors: Vec<ParseError>,
+
+    /// Comments and blank lines are only kept as `Stmt::Trivia` for the formatter.
+    keep_trivia: bool,
+    comments: &'t [Comment],
+    next_comment: usize,
+
+    /// The comments inside the statement being parsed. They follow it once it ends, even if a
+    /// block in it starts first.
+    inner_comments: Vec<Comment>,
 }
 
 /// AKA match in Chapter 6.
@@ -23,42 +39,98 @@ macro_rules! matches {
 
 impl<'t> Parser<'t> {
     pub fn new(tokens: &'t Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            keep_trivia: false,
+            comments: &[],
+            next_comment: 0,
+            inner_comments: Vec::new(),
+        }
+    }
+
+    /// A parser that keeps comments and blank lines between declarations as `Stmt::Trivia`.
+    pub fn with_trivia(tokens: &'t Vec<Token>, comments: &'t [Comment]) -> Self {
+        Parser {
+            keep_trivia: true,
+            comments,
+            ..Parser::new(tokens)
+        }
+    }
+
+    /// Returns the statements, or every syntax error in the source.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+        let (statements, errors) = self.parse_with_errors();
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, Error> {
+    /// Parses as much as possible. Each declaration with a syntax error becomes a `Stmt::Error`.
+    pub fn parse_with_errors(&mut self) -> (Vec<Stmt>, Vec<ParseError>) {
         let mut statments: Vec<Stmt> = Vec::new();
         while !self.is_at_end() {
-            statments.push(self.declaration()?);
+            self.trivia(&mut statments);
+            if !self.is_at_end() {
+                statments.push(self.declaration());
+            }
         }
-        Ok(statments)
+        self.trivia(&mut statments);
+        (statments, std::mem::take(&mut self.errors))
     }
 
     fn expression(&mut self) -> Result<Expr, Error> {
         self.assignment()
     }
 
-    fn declaration(&mut self) -> Result<Stmt, Error> {
+    fn declaration(&mut self) -> Stmt {
         let statement = if matches!(self, TokenType::Var) {
             self.var_declaration()
         } else if matches!(self, TokenType::Class) {
             self.class_declaration()
         } else if matches!(self, TokenType::Fun) {
-            self.function("function")
+            self.function(FunctionKind::Function)
+        } else if matches!(self, TokenType::Import) {
+            self.import_declaration()
         } else {
             self.statement()
         };
 
-        match statement {
-            Err(Error::Parse) => {
-                self.synchronize();
-                Ok(Stmt::Null)
-            }
-            other => other,
-        }
+        statement.unwrap_or_else(|_| {
+            self.synchronize();
+            Stmt::Error
+        })
+    }
+
+    fn import_declaration(&mut self) -> Result<Stmt, Error> {
+        let keyword: Token = self.previous().clone();
+        let path = match &self.peek().tpe {
+            TokenType::String { literal } => literal.clone(),
+            _ => return Err(self.error_at_current("Expect module path after 'import'.")),
+        };
+        self.advance();
+
+        // `as` is not a keyword so it can still be used as a name elsewhere.
+        let name = if self.check(TokenType::Identifier) && self.peek().lexeme == "as" {
+            self.advance();
+            Some(self.consume(TokenType::Identifier, "Expect module name after 'as'.")?)
+        } else {
+            None
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after import.")?;
+        Ok(Stmt::Import {
+            keyword,
+            path,
+            name,
+        })
     }
 
     fn class_declaration(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         let name = self.consume(TokenType::Identifier, "Expect class name.")?;
 
         let superclass = if matches!(self, TokenType::Less) {
@@ -71,20 +143,84 @@ impl<'t> Parser<'t> {
         self.consume(TokenType::LeftBrace, "Expect '{' before class body,")?;
 
         let mut methods: Vec<Stmt> = Vec::new();
+        self.trivia(&mut methods);
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            methods.push(self.function("method")?);
+            methods.push(self.class_member()?);
+            self.trivia(&mut methods);
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
         Ok(Stmt::Class {
+            start,
             name,
-            superclass: superclass.map(|name| Expr::Variable { name }),
+            superclass: superclass.map(|name| Expr::Variable {
+                name,
+                id: self.next_id(),
+            }),
             methods,
         })
     }
 
+    /// A method, class method, getter, setter or typed field like `x: Number;`.
+    fn class_member(&mut self) -> Result<Stmt, Error> {
+        if matches!(self, TokenType::Class) {
+            return self.function(FunctionKind::ClassMethod);
+        }
+        // `set` is not a keyword so it can still be a method name.
+        if self.check(TokenType::Identifier)
+            && self.peek().lexeme == "set"
+            && self.check_next(TokenType::Identifier)
+        {
+            self.advance();
+            return self.function(FunctionKind::Setter);
+        }
+        if !self.check_next(TokenType::Colon) && !self.check_next(TokenType::LeftBrace) {
+            return self.function(FunctionKind::Method);
+        }
+
+        // A getter has no parameter list, and a field no body.
+        let name = self.consume(TokenType::Identifier, "Expect method name.")?;
+        let annotation = self.annotation()?;
+        if annotation.is_some() && matches!(self, TokenType::Semicolon) {
+            return Ok(Stmt::Var {
+                start: name.span,
+                name,
+                annotation,
+                initializer: None,
+            });
+        }
+        self.consume(TokenType::LeftBrace, "Expect '{' before getter body.")?;
+        Ok(Stmt::Function {
+            start: name.span,
+            name,
+            params: Vec::new(),
+            body: self.block()?,
+            signature: Signature {
+                params: Vec::new(),
+                returns: annotation,
+            },
+            kind: FunctionKind::Getter,
+        })
+    }
+
+    /// An optional `: Type` after a name.
+    fn annotation(&mut self) -> Result<Option<Token>, Error> {
+        if matches!(self, TokenType::Colon) {
+            Ok(Some(self.consume(
+                TokenType::Identifier,
+                "Expect type after ':'.",
+            )?))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn statement(&mut self) -> Result<Stmt, Error> {
-        if matches!(self, TokenType::For) {
+        if matches!(self, TokenType::Break) {
+            self.break_statement()
+        } else if matches!(self, TokenType::Continue) {
+            self.continue_statement()
+        } else if matches!(self, TokenType::For) {
             self.for_statement()
         } else if matches!(self, TokenType::If) {
             self.if_statement()
@@ -92,10 +228,15 @@ impl<'t> Parser<'t> {
             self.print_statement()
         } else if matches!(self, TokenType::Return) {
             self.return_statement()
+        } else if matches!(self, TokenType::Throw) {
+            self.throw_statement()
+        } else if matches!(self, TokenType::Try) {
+            self.try_statement()
         } else if matches!(self, TokenType::While) {
             self.while_statement()
         } else if matches!(self, TokenType::LeftBrace) {
             Ok(Stmt::Block {
+                start: self.previous().span,
                 statements: self.block()?,
             })
         } else {
@@ -103,7 +244,20 @@ impl<'t> Parser<'t> {
         }
     }
 
+    fn break_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword: Token = self.previous().clone();
+        self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
+        Ok(Stmt::Break { keyword })
+    }
+
+    fn continue_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword: Token = self.previous().clone();
+        self.consume(TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+        Ok(Stmt::Continue { keyword })
+    }
+
     fn for_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         let initializer = if matches!(self, TokenType::Semicolon) {
@@ -128,24 +282,20 @@ impl<'t> Parser<'t> {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let mut body = self.statement()?;
-
-        if let Some(inc) = increment {
-            let inc_stmt = Stmt::Expression { expression: inc };
-            body = Stmt::Block {
-                statements: vec![body, inc_stmt],
-            }
-        }
-
-        body = Stmt::While {
+        let mut body = Stmt::While {
+            start,
             condition: condition.unwrap_or(Expr::Literal {
                 value: LiteralValue::Boolean(true),
+                token: None,
             }),
-            body: Box::new(body),
+            body: Box::new(self.statement()?),
+            increment,
+            for_loop: true,
         };
 
         if let Some(init_stmt) = initializer {
             body = Stmt::Block {
+                start,
                 statements: vec![init_stmt, body],
             }
         }
@@ -154,6 +304,7 @@ impl<'t> Parser<'t> {
     }
 
     fn if_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
@@ -166,6 +317,7 @@ impl<'t> Parser<'t> {
         };
 
         Ok(Stmt::If {
+            start,
             condition,
             else_branch,
             then_branch,
@@ -173,9 +325,13 @@ impl<'t> Parser<'t> {
     }
 
     fn print_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print { expression: value })
+        Ok(Stmt::Print {
+            start,
+            expression: value,
+        })
     }
 
     fn return_statement(&mut self) -> Result<Stmt, Error> {
@@ -190,8 +346,50 @@ impl<'t> Parser<'t> {
         Ok(Stmt::Return { keyword, value })
     }
 
+    fn throw_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword: Token = self.previous().clone();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after thrown value.")?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        let catch = if matches!(self, TokenType::Catch) {
+            self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+            let name = self.consume(TokenType::Identifier, "Expect name of caught value.")?;
+            self.consume(TokenType::RightParen, "Expect ')' after caught value.")?;
+            self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+            Some((name, self.block()?))
+        } else {
+            None
+        };
+
+        let finally = if matches!(self, TokenType::Finally) {
+            self.consume(TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            Some(self.block()?)
+        } else {
+            None
+        };
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.error_at_current("Expect 'catch' or 'finally' after try block."));
+        }
+        Ok(Stmt::Try {
+            start,
+            body,
+            catch,
+            finally,
+        })
+    }
+
     fn var_declaration(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        let annotation = self.annotation()?;
 
         let initializer = if matches!(self, TokenType::Equal) {
             Some(self.expression()?)
@@ -203,15 +401,27 @@ impl<'t> Parser<'t> {
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
         )?;
-        Ok(Stmt::Var { name, initializer })
+        Ok(Stmt::Var {
+            start,
+            name,
+            annotation,
+            initializer,
+        })
     }
 
     fn while_statement(&mut self) -> Result<Stmt, Error> {
+        let start = self.previous().span;
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
         let body = Box::new(self.statement()?);
-        Ok(Stmt::While { condition, body })
+        Ok(Stmt::While {
+            start,
+            condition,
+            body,
+            increment: None,
+            for_loop: false,
+        })
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, Error> {
@@ -220,23 +430,34 @@ impl<'t> Parser<'t> {
         Ok(Stmt::Expression { expression: expr })
     }
 
-    fn function(&mut self, kind: &str) -> Result<Stmt, Error> {
+    fn function(&mut self, kind: FunctionKind) -> Result<Stmt, Error> {
+        // Methods start with their name, the others with a keyword.
+        let start = match kind {
+            FunctionKind::Method => self.peek().span,
+            _ => self.previous().span,
+        };
+        let kind_name = match kind {
+            FunctionKind::Function => "function",
+            _ => "method",
+        };
         let name = self.consume(
             TokenType::Identifier,
-            format!("Expect {} name.", kind).as_str(),
+            format!("Expect {} name.", kind_name).as_str(),
         )?;
         self.consume(
             TokenType::LeftParen,
-            format!("Expect '(' after {} name.", kind).as_str(),
+            format!("Expect '(' after {} name.", kind_name).as_str(),
         )?;
         let mut params: Vec<Token> = Vec::new();
+        let mut signature = Signature::default();
         if !self.check(TokenType::RightParen) {
             loop {
                 if params.len() >= 255 {
                     // We are not returning an error here.
-                    self.error(self.peek(), "Cannot have more than 255 parameters.");
+                    self.error_at_current("Cannot have more than 255 parameters.");
                 }
                 params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                signature.params.push(self.annotation()?);
 
                 if !matches!(self, TokenType::Comma) {
                     break;
@@ -244,20 +465,34 @@ impl<'t> Parser<'t> {
             }
         }
         self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        signature.returns = self.annotation()?;
+        if kind == FunctionKind::Setter && params.len() != 1 {
+            // Like too many parameters, this does not stop the parser.
+            self.error(&name, "A setter must have one parameter.");
+        }
 
         self.consume(
             TokenType::LeftBrace,
-            format!("Expect '{{' before {} body.", kind).as_str(),
+            format!("Expect '{{' before {} body.", kind_name).as_str(),
         )?;
         let body = self.block()?;
-        Ok(Stmt::Function { name, params, body })
+        Ok(Stmt::Function {
+            start,
+            name,
+            params,
+            body,
+            signature,
+            kind,
+        })
     }
 
     fn block(&mut self) -> Result<Vec<Stmt>, Error> {
         let mut statements: Vec<Stmt> = Vec::new();
 
+        self.trivia(&mut statements);
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.push(self.declaration());
+            self.trivia(&mut statements);
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
@@ -270,20 +505,36 @@ impl<'t> Parser<'t> {
         if matches!(self, TokenType::Equal) {
             let value = Box::new(self.assignment()?);
 
-            if let Expr::Variable { name } = expr {
-                return Ok(Expr::Assign { name, value });
+            if let Expr::Variable { name, .. } = expr {
+                return Ok(Expr::Assign {
+                    name,
+                    value,
+                    id: self.next_id(),
+                });
             } else if let Expr::Get { object, name } = expr {
                 return Ok(Expr::Set {
                     object,
                     name,
                     value,
                 });
+            } else if let Expr::Index {
+                object,
+                bracket,
+                index,
+            } = expr
+            {
+                return Ok(Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value,
+                });
             }
 
             // We are just reporting the error but not return them.
             // See note in http://craftinginterpreters.com/statements-and-state.html#assignment-syntax.
-            let equals = self.previous();
-            self.error(equals, "Invalid assignment target.");
+            let equals = self.previous().clone();
+            self.error(&equals, "Invalid assignment target.");
         }
 
         Ok(expr)
@@ -297,7 +548,7 @@ impl<'t> Parser<'t> {
             let right: Expr = self.and_()?;
             expr = Expr::Logical {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             };
         }
@@ -313,7 +564,7 @@ impl<'t> Parser<'t> {
             let right: Expr = self.equality()?;
             expr = Expr::Logical {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             };
         }
@@ -345,11 +596,21 @@ impl<'t> Parser<'t> {
         token_type == self.peek().tpe
     }
 
+    /// Checks the token after the current one.
+    fn check_next(&self, token_type: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.tpe == token_type)
+    }
+
     fn consume(&mut self, tpe: TokenType, message: &str) -> Result<Token, Error> {
-        if self.check(tpe) {
+        if self.check(tpe.clone()) {
             Ok(self.advance().clone())
         } else {
-            Err(self.error(self.peek(), message))
+            let token = self.peek().clone();
+            self.errors
+                .push(ParseError::new(&token, message, Some(tpe)));
+            Err(Error::Parse)
         }
     }
 
@@ -366,11 +627,16 @@ impl<'t> Parser<'t> {
             .expect("Previous was empty.")
     }
 
-    fn error(&self, token: &Token, message: &str) -> Error {
-        parser_error(token, message);
+    fn error(&mut self, token: &Token, message: &str) -> Error {
+        self.errors.push(ParseError::new(token, message, None));
         Error::Parse
     }
 
+    fn error_at_current(&mut self, message: &str) -> Error {
+        let token = self.peek().clone();
+        self.error(&token, message)
+    }
+
     fn synchronize(&mut self) {
         self.advance();
 
@@ -387,12 +653,81 @@ impl<'t> Parser<'t> {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Import
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::Break
+                | TokenType::Continue => return,
                 _ => self.advance(),
             };
         }
     }
 
+    /// Adds the comments before the next token, and blank lines between declarations.
+    fn trivia(&mut self, statements: &mut Vec<Stmt>) {
+        if !self.keep_trivia {
+            return;
+        }
+
+        let mut last_line = if self.current > 0 {
+            self.previous().line
+        } else {
+            // Don't start the file with a blank line.
+            self.peek().line
+        };
+        let mut trivia = Vec::new();
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.start > self.peek().span.start {
+                break;
+            }
+            self.next_comment += 1;
+
+            if self.current > 0 && comment.span.start < self.previous().span.start {
+                self.inner_comments.push(comment.clone());
+            } else if self.current > 0 && comment.line == self.previous().line {
+                trivia.push(Trivia::TrailingComment(comment.text.clone()));
+                last_line = comment.line;
+            } else {
+                if comment.line > last_line + 1 {
+                    trivia.push(Trivia::BlankLine);
+                }
+                trivia.push(Trivia::Comment(comment.text.clone()));
+                last_line = comment.line;
+            }
+        }
+
+        // The comments inside the last statement go right after it. Those before the first
+        // statement of a block are in the header of the statement around it.
+        let last = statements
+            .iter()
+            .rev()
+            .find(|statement| !std::matches!(statement, Stmt::Trivia { .. }));
+        if let Some(start) = last.and_then(Stmt::span).map(|span| span.start) {
+            let (inner, outer) = std::mem::take(&mut self.inner_comments)
+                .into_iter()
+                .partition(|comment| comment.span.start >= start);
+            self.inner_comments = outer;
+            for comment in inner {
+                statements.push(Stmt::Trivia {
+                    trivia: Trivia::InnerComment(comment),
+                });
+            }
+        }
+        statements.extend(trivia.into_iter().map(|trivia| Stmt::Trivia { trivia }));
+
+        let at_end = self.is_at_end() || self.check(TokenType::RightBrace);
+        if !at_end && self.peek().line > last_line + 1 {
+            statements.push(Stmt::Trivia {
+                trivia: Trivia::BlankLine,
+            });
+        }
+    }
+
+    fn next_id(&self) -> ExprId {
+        NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+    }
+
     fn peek(&self) -> &Token {
         self.tokens
             .get(self.current)
@@ -480,7 +815,15 @@ impl<'t> Parser<'t> {
                 let name = self.consume(TokenType::Identifier, "Expect property after '.'.")?;
                 expr = Expr::Get {
                     object: Box::new(expr),
-                    name: name,
+                    name,
+                }
+            } else if matches!(self, TokenType::LeftBracket) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index {
+                    object: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
                 }
             } else {
                 break;
@@ -496,7 +839,7 @@ impl<'t> Parser<'t> {
             loop {
                 if arguments.len() >= 255 {
                     // We are just reporting the error but not return them.
-                    self.error(self.peek(), "Cannot have more than 255 arguments.");
+                    self.error_at_current("Cannot have more than 255 arguments.");
                 }
                 arguments.push(self.expression()?);
                 if !matches!(self, TokenType::Comma) {
@@ -518,18 +861,23 @@ impl<'t> Parser<'t> {
         let expr = match &self.peek().tpe {
             TokenType::False => Expr::Literal {
                 value: LiteralValue::Boolean(false),
+                token: Some(self.peek().clone()),
             },
             TokenType::True => Expr::Literal {
                 value: LiteralValue::Boolean(true),
+                token: Some(self.peek().clone()),
             },
             TokenType::Nil => Expr::Literal {
                 value: LiteralValue::Null,
+                token: Some(self.peek().clone()),
             },
             TokenType::String { literal } => Expr::Literal {
                 value: LiteralValue::String(literal.clone()),
+                token: Some(self.peek().clone()),
             },
             TokenType::Number { literal } => Expr::Literal {
-                value: LiteralValue::Number(literal.clone()),
+                value: LiteralValue::Number(*literal),
+                token: Some(self.peek().clone()),
             },
             TokenType::Super => {
                 let keyword = self.advance().clone();
@@ -539,24 +887,62 @@ impl<'t> Parser<'t> {
 
                 // We already advance so we cut it short here.
                 return Ok(Expr::Super {
-                    keyword: keyword,
+                    keyword,
                     method,
+                    id: self.next_id(),
                 });
             }
             TokenType::This => Expr::This {
                 keyword: self.peek().clone(),
+                id: self.next_id(),
             },
             TokenType::Identifier => Expr::Variable {
                 name: self.peek().clone(),
+                id: self.next_id(),
             },
             TokenType::LeftParen => {
+                self.advance();
                 let expr = self.expression()?;
                 self.consume(TokenType::RightParen, "Expected ')' after expression.")?;
-                Expr::Grouping {
+
+                // We already advance so we cut it short here.
+                return Ok(Expr::Grouping {
                     expression: Box::new(expr),
+                });
+            }
+            TokenType::LeftBracket => {
+                self.advance();
+                let mut elements: Vec<Expr> = Vec::new();
+                if !self.check(TokenType::RightBracket) {
+                    loop {
+                        elements.push(self.expression()?);
+                        if !matches!(self, TokenType::Comma) {
+                            break;
+                        }
+                    }
                 }
+                let bracket =
+                    self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+                return Ok(Expr::List { bracket, elements });
             }
-            _ => return Err(self.error(self.peek(), "Expect expression.")),
+            // A '{' that starts a statement is a block, see `statement`.
+            TokenType::LeftBrace => {
+                self.advance();
+                let mut entries: Vec<(Expr, Expr)> = Vec::new();
+                if !self.check(TokenType::RightBrace) {
+                    loop {
+                        let key = self.expression()?;
+                        self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                        entries.push((key, self.expression()?));
+                        if !matches!(self, TokenType::Comma) {
+                            break;
+                        }
+                    }
+                }
+                let brace = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+                return Ok(Expr::Map { brace, entries });
+            }
+            _ => return Err(self.error_at_current("Expect expression.")),
         };
 
         self.advance();
@@ -573,13 +959,65 @@ mod tests {
 
     #[test]
     fn test_parser() {
-        let mut scanner = Scanner::new("-123 * 45.67".to_string());
+        let mut scanner = Scanner::new("-123 * 45.67;".to_string());
         let tokens = scanner.scan_tokens();
 
         let mut parser = Parser::new(tokens);
         let statements = parser.parse().expect("Could not parse sample code.");
-        let printer = AstPrinter;
+        let mut printer = AstPrinter;
+
+        assert_eq!(
+            printer.print_statements(&statements).unwrap(),
+            "(; (* (- 123) 45.67))"
+        );
+    }
+
+    #[test]
+    fn all_errors_and_a_partial_tree() {
+        let mut scanner = Scanner::new("var = 1;\nprint 2;\n{ print (3; }\nprint;".to_string());
+        let tokens = scanner.scan_tokens();
 
-        //        assert_eq!(printer.print(statements).unwrap(), "(* (- 123) 45.67)");
+        let errors = Parser::new(tokens)
+            .parse()
+            .expect_err("Parsed invalid code.");
+        let errors: Vec<(&str, Option<TokenType>, &str)> = errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.expected.clone(), e.found.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("Expect variable name.", Some(TokenType::Identifier), "'='"),
+                (
+                    "Expected ')' after expression.",
+                    Some(TokenType::RightParen),
+                    "';'"
+                ),
+                ("Expect expression.", None, "';'"),
+            ]
+        );
+
+        let (statements, _) = Parser::new(tokens).parse_with_errors();
+        match statements.as_slice() {
+            [Stmt::Error, Stmt::Print { .. }, Stmt::Block { statements, .. }, Stmt::Error] => {
+                assert!(std::matches!(statements.as_slice(), [Stmt::Error]))
+            }
+            _ => panic!("Unexpected partial tree."),
+        }
+    }
+
+    #[test]
+    fn recover_at_every_statement_keyword() {
+        let source = "fun f() { while (true) { var a = ) break; var b = ) continue; } } var c = ) throw 4; var d = ) try {} catch (e) {} var e = ) import \"m.lox\";";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+
+        let (statements, errors) = Parser::new(tokens).parse_with_errors();
+        assert_eq!(errors.len(), 5);
+        match statements.as_slice() {
+            [Stmt::Function { .. }, Stmt::Error, Stmt::Throw { .. }, Stmt::Error, Stmt::Try { .. }, Stmt::Error, Stmt::Import { .. }] =>
+                {}
+            other => panic!("Unexpected partial tree {:?}.", other),
+        }
     }
 }
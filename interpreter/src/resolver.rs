@@ -1,11 +1,16 @@
-use crate::error::{report, Error};
+use crate::error::{at, report, Error};
 use crate::interpreter::Interpreter;
+use crate::object::Object;
 use crate::syntax::{expr, stmt};
-use crate::syntax::{Expr, LiteralValue, Stmt};
-use crate::token::{Token, TokenType};
+use crate::syntax::{Expr, ExprId, FunctionKind, LiteralValue, Stmt, Trivia};
+use crate::token::{Span, Token};
+
+use diagnostics::Diagnostic;
 
 use std::collections::HashMap;
+use std::fmt;
 use std::mem;
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 enum FunctionType {
@@ -13,6 +18,12 @@ enum FunctionType {
     Function,
     Initializer,
     Method,
+
+    /// A method called on the class. Its `this` is the class.
+    ClassMethod,
+
+    /// The value a setter returns is dropped.
+    Setter,
 }
 
 #[derive(Debug, Clone)]
@@ -22,37 +33,247 @@ enum ClassType {
     Subclass,
 }
 
+/// A warning about code that runs but is probably wrong. The resolver only checks the lints it
+/// is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    ArgumentCount,
+    Shadowing,
+    UndeclaredAssignment,
+    UnreachableCode,
+    UnusedParameter,
+    UnusedVariable,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [
+        Lint::ArgumentCount,
+        Lint::Shadowing,
+        Lint::UndeclaredAssignment,
+        Lint::UnreachableCode,
+        Lint::UnusedParameter,
+        Lint::UnusedVariable,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::ArgumentCount => "argument-count",
+            Lint::Shadowing => "shadowing",
+            Lint::UndeclaredAssignment => "undeclared-assignment",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedVariable => "unused-variable",
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Lint::ALL
+            .iter()
+            .find(|lint| lint.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown lint '{}'.", s))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LocalKind {
+    Variable,
+    Parameter,
+
+    /// Functions, classes, modules and caught values, which are not reported when unused.
+    Other,
+}
+
+/// A local variable of a scope.
+#[derive(Debug, Clone)]
+struct Local {
+    /// The index of the variable in its `Environment`.
+    slot: usize,
+
+    /// Whether the initializer of the variable was resolved.
+    defined: bool,
+
+    /// The name in the declaration. Implicit variables have an empty span.
+    declared: Span,
+
+    kind: LocalKind,
+
+    /// Whether the variable is read anywhere. Assigning it does not count.
+    used: bool,
+
+    /// The number of arguments a call takes, if the variable is a function or class.
+    arity: Option<usize>,
+}
+
+/// A global that lints know about.
+#[derive(Debug, Clone)]
+struct Global {
+    /// The name in the declaration. Globals defined before, like natives, have none.
+    declared: Option<Span>,
+    arity: Option<usize>,
+}
+
+/// A name in an expression and the local it resolved to, for the language server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: Span,
+
+    /// The name in the local's declaration, or `None` for globals, which are looked up by name.
+    pub declared: Option<Span>,
+}
+
 pub struct Resolver<'i> {
     interpreter: &'i mut Interpreter,
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<String, Local>>,
     current_function: FunctionType,
     current_class: ClassType,
+    in_loop: bool,
     pub had_error: bool,
+    pub references: Vec<Reference>,
+
+    lints: Vec<Lint>,
+
+    /// The globals of the source and the interpreter. Only collected for lints.
+    globals: HashMap<String, Global>,
+
+    /// Whether a plain `import` defines globals that are only known at runtime.
+    unknown_globals: bool,
 }
 
 impl<'i> Resolver<'i> {
     pub fn new(interpreter: &'i mut Interpreter) -> Self {
         Resolver {
-            interpreter: interpreter,
+            interpreter,
             scopes: Vec::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            in_loop: false,
             had_error: false,
+            references: Vec::new(),
+            lints: Vec::new(),
+            globals: HashMap::new(),
+            unknown_globals: false,
+        }
+    }
+
+    /// A resolver that also reports the given lints as warnings.
+    pub fn with_lints(interpreter: &'i mut Interpreter, lints: &[Lint]) -> Self {
+        Resolver {
+            lints: lints.to_vec(),
+            ..Resolver::new(interpreter)
         }
     }
 
     fn resolve_stmt(&mut self, statement: &Stmt) {
-        statement.accept(self);
+        let _ = statement.accept(self);
     }
 
-    pub fn resolve_stmts(&mut self, statements: &Vec<Stmt>) {
+    pub fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        if self.scopes.is_empty() && !self.lints.is_empty() {
+            self.declare_globals(statements);
+        }
+
+        let mut jump: Option<&Token> = None;
+        let mut unreachable = false;
         for statement in statements {
+            if let (Some(keyword), false) = (jump, unreachable) {
+                if !std::matches!(statement, Stmt::Trivia { .. }) {
+                    unreachable = true;
+                    let message = format!("Unreachable code after '{}'.", keyword.lexeme);
+                    let diagnostic = match statement.span() {
+                        Some(span) => Diagnostic::warning(&message)
+                            .with_primary(span, "")
+                            .with_secondary(keyword.span, "any code after this is unreachable"),
+                        None => Diagnostic::warning(&message).with_primary(keyword.span, ""),
+                    };
+                    self.report_lint(Lint::UnreachableCode, diagnostic);
+                }
+            }
+
             self.resolve_stmt(statement);
+            match statement {
+                Stmt::Break { keyword }
+                | Stmt::Continue { keyword }
+                | Stmt::Return { keyword, .. }
+                | Stmt::Throw { keyword, .. } => jump = jump.or(Some(keyword)),
+                _ => (),
+            }
+        }
+    }
+
+    /// Collects the globals the statements declare, and those the interpreter already has.
+    /// Globals can be used before they are declared, e.g. in a function body.
+    fn declare_globals(&mut self, statements: &[Stmt]) {
+        for (name, value) in self.interpreter.globals.borrow().globals() {
+            let arity = match value {
+                Object::Callable(function) => Some(function.arity()),
+                _ => None,
+            };
+            self.globals.insert(
+                name,
+                Global {
+                    declared: None,
+                    arity,
+                },
+            );
+        }
+
+        for statement in statements {
+            let (name, arity) = match statement {
+                Stmt::Class {
+                    name,
+                    superclass,
+                    methods,
+                    ..
+                } => {
+                    let superclass = superclass.as_ref().and_then(|superclass| match superclass {
+                        Expr::Variable { name, .. } => Some(&name.lexeme),
+                        _ => None,
+                    });
+                    let inherited = superclass
+                        .and_then(|superclass| self.globals.get(superclass))
+                        .and_then(|global| global.arity);
+                    (name, class_arity(methods, superclass.is_some(), inherited))
+                }
+                Stmt::Function { name, params, .. } => (name, Some(params.len())),
+                Stmt::Import {
+                    name: Some(name), ..
+                }
+                | Stmt::Var { name, .. } => (name, None),
+                Stmt::Import { name: None, .. } => {
+                    self.unknown_globals = true;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            // A name declared twice might be either.
+            let arity = match self.globals.get(&name.lexeme) {
+                Some(global) if global.declared.is_some() && global.arity != arity => None,
+                _ => arity,
+            };
+            self.globals.insert(
+                name.lexeme.clone(),
+                Global {
+                    declared: Some(name.span),
+                    arity,
+                },
+            );
         }
     }
 
     fn resolve_expr(&mut self, expression: &Expr) {
-        expression.accept(self);
+        let _ = expression.accept(self);
     }
 
     fn begin_scope(&mut self) {
@@ -60,72 +281,202 @@ impl<'i> Resolver<'i> {
     }
 
     fn end_scope(&mut self) {
-        self.scopes.pop();
+        let scope = self.scopes.pop().expect("Scopes is empty.");
+        let mut unused: Vec<(&String, &Local)> = scope
+            .iter()
+            .filter(|(name, local)| !local.used && !name.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(_, local)| local.declared.start);
+        for (name, local) in unused {
+            match local.kind {
+                LocalKind::Variable => self.warn(
+                    Lint::UnusedVariable,
+                    local.declared,
+                    &format!("Unused variable '{}'.", name),
+                ),
+                LocalKind::Parameter => self.warn(
+                    Lint::UnusedParameter,
+                    local.declared,
+                    &format!("Unused parameter '{}'.", name),
+                ),
+                LocalKind::Other => (),
+            }
+        }
     }
 
-    fn declare(&mut self, name: &Token) {
-        let mut already_defined: bool = false;
-        match self.scopes.last_mut() {
-            Some(ref mut scope) => {
-                already_defined = scope.contains_key(&name.lexeme);
-                scope.insert(name.lexeme.clone(), false);
-            }
-            None => (),
-        };
+    fn declare(&mut self, name: &Token, kind: LocalKind) {
+        if !self.scopes.is_empty() {
+            self.check_shadowing(name);
+        }
+
+        let mut previous: Option<Local> = None;
+        if let Some(scope) = self.scopes.last_mut() {
+            let slot = scope.len();
+            previous = scope.insert(
+                name.lexeme.clone(),
+                Local {
+                    slot,
+                    defined: false,
+                    declared: name.span,
+                    kind,
+                    used: false,
+                    arity: None,
+                },
+            );
+        }
 
         // Report an error if the variable was already defined.
-        if already_defined {
-            self.error(
-                name,
-                "Variable with this name already declared in this scope.",
+        if let Some(previous) = previous {
+            self.report(
+                at(
+                    name,
+                    "Variable with this name already declared in this scope.",
+                )
+                .with_secondary(previous.declared, "previously declared here"),
+            );
+        }
+    }
+
+    /// Warns if a local has the name of a variable in an enclosing scope or a global of the source.
+    fn check_shadowing(&mut self, name: &Token) {
+        if !self.lints.contains(&Lint::Shadowing) {
+            return;
+        }
+
+        let outer = self.scopes[..self.scopes.len() - 1]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.lexeme))
+            .map(|local| local.declared)
+            .filter(|declared| declared.start != declared.end)
+            .or_else(|| self.globals.get(&name.lexeme).and_then(|g| g.declared));
+        if let Some(outer) = outer {
+            let message = format!(
+                "'{}' shadows a variable of an outer scope. [{}]",
+                name.lexeme,
+                Lint::Shadowing
+            );
+            report(
+                &Diagnostic::warning(&message)
+                    .with_primary(name.span, "")
+                    .with_secondary(outer, "shadowed variable declared here"),
             );
         }
     }
 
+    /// Records how many arguments calling a local function or class takes.
+    fn set_arity(&mut self, name: &Token, arity: Option<usize>) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.arity = arity;
+        }
+    }
+
+    /// The number of arguments calling a variable takes, if it is a known function or class.
+    fn arity(&self, name: &Token) -> Option<usize> {
+        match self.scopes.iter().rev().find_map(|s| s.get(&name.lexeme)) {
+            Some(local) => local.arity,
+            None => self.globals.get(&name.lexeme).and_then(|g| g.arity),
+        }
+    }
+
     fn define(&mut self, name: &Token) {
-        match self.scopes.last_mut() {
-            Some(ref mut scope) => {
-                scope.insert(name.lexeme.clone(), true);
-            }
-            None => (),
-        };
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
+        }
     }
 
-    fn resolve_function(&mut self, params: &Vec<Token>, body: &Vec<Stmt>, tpe: FunctionType) {
+    /// Begins a scope that only holds the implicitly defined `this` or `super`.
+    fn begin_implicit_scope(&mut self, name: &str) {
+        self.begin_scope();
+        self.scopes.last_mut().expect("Scopes is empty.").insert(
+            name.to_owned(),
+            Local {
+                slot: 0,
+                defined: true,
+                declared: Span::default(),
+                kind: LocalKind::Other,
+                used: false,
+                arity: None,
+            },
+        );
+    }
+
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt], tpe: FunctionType) {
         let enclosing_function = mem::replace(&mut self.current_function, tpe);
+        // A function body is never part of the loop it is declared in.
+        let enclosing_loop = mem::replace(&mut self.in_loop, false);
 
         self.begin_scope();
         for param in params {
-            self.declare(param);
+            self.declare(param, LocalKind::Parameter);
             self.define(param);
         }
         self.resolve_stmts(body);
         self.end_scope();
         self.current_function = enclosing_function;
-    }
-
-    fn resolve_local(&mut self, name: &Token) {
-        for (i, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name.lexeme) {
-                self.interpreter.resolve(name, i);
+        self.in_loop = enclosing_loop;
+    }
+
+    /// Resolves a local and returns whether it was found. Globals are looked up at runtime.
+    fn resolve_local(&mut self, id: ExprId, name: &Token, read: bool) -> bool {
+        let mut declared = None;
+        for (i, scope) in self.scopes.iter_mut().rev().enumerate() {
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                self.interpreter.resolve(id, i, local.slot);
+                local.used |= read;
+                declared = Some(local.declared);
+                break;
             }
         }
+        self.references.push(Reference {
+            name: name.span,
+            declared,
+        });
+        declared.is_some()
     }
 
     fn error(&mut self, token: &Token, message: &str) {
-        if token.tpe == TokenType::EOF {
-            report(token.line, " at end", message);
-        } else {
-            report(token.line, &format!(" at '{}'", token.lexeme), message);
-        }
+        self.report(at(token, message));
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        report(&diagnostic);
         self.had_error = true;
     }
+
+    /// Reports a warning if the lint is checked. The name of the lint is added to the message.
+    fn warn(&mut self, lint: Lint, span: Span, message: &str) {
+        self.report_lint(lint, Diagnostic::warning(message).with_primary(span, ""));
+    }
+
+    /// Reports the warning if the lint is on. The name of the lint is added to the message.
+    fn report_lint(&mut self, lint: Lint, mut diagnostic: Diagnostic) {
+        if self.lints.contains(&lint) {
+            diagnostic.message = format!("{} [{}]", diagnostic.message, lint);
+            report(&diagnostic);
+        }
+    }
 }
 
 impl<'i> expr::Visitor<()> for Resolver<'i> {
-    fn visit_assign_expr(&mut self, name: &Token, value: &Expr) -> Result<(), Error> {
+    fn visit_assign_expr(&mut self, name: &Token, value: &Expr, id: ExprId) -> Result<(), Error> {
         self.resolve_expr(value);
-        self.resolve_local(name);
+        let local = self.resolve_local(id, name, false);
+        if !local && !self.unknown_globals && !self.globals.contains_key(&name.lexeme) {
+            self.warn(
+                Lint::UndeclaredAssignment,
+                name.span,
+                &format!("Assignment to undeclared variable '{}'.", name.lexeme),
+            );
+        }
         Ok(())
     }
 
@@ -143,9 +494,19 @@ impl<'i> expr::Visitor<()> for Resolver<'i> {
     fn visit_call_expr(
         &mut self,
         callee: &Expr,
-        _paren: &Token,
-        arguments: &Vec<Expr>,
+        paren: &Token,
+        arguments: &[Expr],
     ) -> Result<(), Error> {
+        if let Expr::Variable { name, .. } = callee {
+            match self.arity(name) {
+                Some(arity) if arity != arguments.len() => self.warn(
+                    Lint::ArgumentCount,
+                    paren.span,
+                    &format!("Expected {} arguments but got {}.", arity, arguments.len()),
+                ),
+                _ => (),
+            }
+        }
         self.resolve_expr(callee);
         for argument in arguments {
             self.resolve_expr(argument);
@@ -163,6 +524,24 @@ impl<'i> expr::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+    ) -> Result<(), Error> {
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        Ok(())
+    }
+
+    fn visit_list_expr(&mut self, _bracket: &Token, elements: &[Expr]) -> Result<(), Error> {
+        for element in elements {
+            self.resolve_expr(element);
+        }
+        Ok(())
+    }
+
     fn visit_literal_expr(&self, _value: &LiteralValue) -> Result<(), Error> {
         Ok(())
     }
@@ -178,28 +557,56 @@ impl<'i> expr::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
+    fn visit_map_expr(&mut self, _brace: &Token, entries: &[(Expr, Expr)]) -> Result<(), Error> {
+        for (key, value) in entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+        Ok(())
+    }
+
     fn visit_set_expr(&mut self, object: &Expr, _name: &Token, value: &Expr) -> Result<(), Error> {
         self.resolve_expr(value);
         self.resolve_expr(object);
         Ok(())
     }
 
-    fn visit_super_expr(&mut self, keyword: &Token, _method: &Token) -> Result<(), Error> {
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<(), Error> {
+        self.resolve_expr(value);
+        self.resolve_expr(object);
+        self.resolve_expr(index);
+        Ok(())
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        keyword: &Token,
+        _method: &Token,
+        id: ExprId,
+    ) -> Result<(), Error> {
         match self.current_class {
             ClassType::None => self.error(keyword, "Cannot use 'super' outside of a class."),
             ClassType::Class => {
                 self.error(keyword, "Cannot use 'super' in a class with no superclass.")
             }
-            _ => self.resolve_local(keyword),
+            _ => {
+                self.resolve_local(id, keyword, true);
+            }
         }
         Ok(())
     }
 
-    fn visit_this_expr(&mut self, keyword: &Token) -> Result<(), Error> {
+    fn visit_this_expr(&mut self, keyword: &Token, id: ExprId) -> Result<(), Error> {
         if let ClassType::None = self.current_class {
             self.error(keyword, "Cannot use 'this' outside of a class.");
         } else {
-            self.resolve_local(keyword);
+            self.resolve_local(id, keyword, true);
         }
         Ok(())
     }
@@ -209,72 +616,89 @@ impl<'i> expr::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
-    fn visit_variable_expr(&mut self, name: &Token) -> Result<(), Error> {
+    fn visit_variable_expr(&mut self, name: &Token, id: ExprId) -> Result<(), Error> {
         if let Some(scope) = self.scopes.last() {
-            if let Some(flag) = scope.get(&name.lexeme) {
-                if *flag == false {
-                    self.error(name, "Cannot read local variable in its own initializer.");
+            if let Some(local) = scope.get(&name.lexeme) {
+                if !local.defined {
+                    let declared = local.declared;
+                    self.report(
+                        at(name, "Cannot read local variable in its own initializer.")
+                            .with_secondary(declared, "variable declared here"),
+                    );
                 }
             }
         };
-        self.resolve_local(name);
+        self.resolve_local(id, name, true);
         Ok(())
     }
 }
 
 impl<'i> stmt::Visitor<()> for Resolver<'i> {
-    fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) -> Result<(), Error> {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<(), Error> {
         self.begin_scope();
         self.resolve_stmts(statements);
         self.end_scope();
         Ok(())
     }
 
+    fn visit_break_stmt(&mut self, keyword: &Token) -> Result<(), Error> {
+        if !self.in_loop {
+            self.error(keyword, "Cannot use 'break' outside of a loop.");
+        }
+        Ok(())
+    }
+
     fn visit_class_stmt(
         &mut self,
         name: &Token,
         superclass: &Option<Expr>,
-        methods: &Vec<Stmt>,
+        methods: &[Stmt],
     ) -> Result<(), Error> {
         let enclosing_class = mem::replace(&mut self.current_class, ClassType::Class);
 
-        self.declare(name);
+        self.declare(name, LocalKind::Other);
         self.define(name);
 
         if let Some(Expr::Variable {
             name: superclass_name,
+            id,
         }) = superclass
         {
+            let inherited = self.arity(superclass_name);
+            self.set_arity(name, class_arity(methods, true, inherited));
+
             if name.lexeme == superclass_name.lexeme {
                 self.error(superclass_name, "A class cannot inherit from itself.");
             }
 
             self.current_class = ClassType::Subclass;
-            self.resolve_local(superclass_name);
+            self.resolve_local(*id, superclass_name, true);
 
-            self.begin_scope();
-            self.scopes
-                .last_mut()
-                .expect("Scopes is empty.")
-                .insert("super".to_owned(), true);
+            self.begin_implicit_scope("super");
+        } else {
+            self.set_arity(name, class_arity(methods, false, None));
         }
 
-        self.begin_scope();
-        self.scopes
-            .last_mut()
-            .expect("Scopes is empty.")
-            .insert("this".to_owned(), true);
+        self.begin_implicit_scope("this");
 
         for method in methods {
-            if let Stmt::Function { name, params, body } = method {
-                let declaration = if name.lexeme == "init" {
-                    FunctionType::Initializer
-                } else {
-                    FunctionType::Method
+            if let Stmt::Function {
+                name,
+                params,
+                body,
+                kind,
+                ..
+            } = method
+            {
+                let declaration = match kind {
+                    FunctionKind::ClassMethod => FunctionType::ClassMethod,
+                    FunctionKind::Setter => FunctionType::Setter,
+                    _ if name.lexeme == "init" && *kind == FunctionKind::Method => {
+                        FunctionType::Initializer
+                    }
+                    _ => FunctionType::Method,
                 };
                 self.resolve_function(params, body, declaration);
-            } else {
-                unreachable!()
             }
         }
 
@@ -289,6 +713,17 @@ impl<'i> stmt::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
+    fn visit_continue_stmt(&mut self, keyword: &Token) -> Result<(), Error> {
+        if !self.in_loop {
+            self.error(keyword, "Cannot use 'continue' outside of a loop.");
+        }
+        Ok(())
+    }
+
+    fn visit_error_stmt(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
     fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<(), Error> {
         self.resolve_expr(expression);
         Ok(())
@@ -297,11 +732,12 @@ impl<'i> stmt::Visitor<()> for Resolver<'i> {
     fn visit_function_stmt(
         &mut self,
         name: &Token,
-        params: &Vec<Token>,
-        body: &Vec<Stmt>,
+        params: &[Token],
+        body: &[Stmt],
     ) -> Result<(), Error> {
-        self.declare(name);
+        self.declare(name, LocalKind::Other);
         self.define(name);
+        self.set_arity(name, Some(params.len()));
 
         self.resolve_function(params, body, FunctionType::Function);
         Ok(())
@@ -321,6 +757,23 @@ impl<'i> stmt::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
+    fn visit_import_stmt(
+        &mut self,
+        keyword: &Token,
+        _path: &str,
+        name: &Option<Token>,
+    ) -> Result<(), Error> {
+        // A plain import defines all globals of the module, which only works by name.
+        if !self.scopes.is_empty() {
+            self.error(keyword, "Can only import at the top level.");
+        }
+        if let Some(name) = name {
+            self.declare(name, LocalKind::Other);
+            self.define(name);
+        }
+        Ok(())
+    }
+
     fn visit_print_stmt(&mut self, expression: &Expr) -> Result<(), Error> {
         self.resolve_expr(expression);
         Ok(())
@@ -332,16 +785,56 @@ impl<'i> stmt::Visitor<()> for Resolver<'i> {
         }
 
         if let Some(return_value) = value {
-            if let FunctionType::Initializer = self.current_function {
-                self.error(keyword, "Cannot return value from initializer.");
+            match self.current_function {
+                FunctionType::Initializer => {
+                    self.error(keyword, "Cannot return value from initializer.")
+                }
+                FunctionType::Setter => self.error(keyword, "Cannot return value from setter."),
+                _ => (),
             }
             self.resolve_expr(return_value);
         }
         Ok(())
     }
 
+    fn visit_throw_stmt(&mut self, _keyword: &Token, value: &Expr) -> Result<(), Error> {
+        self.resolve_expr(value);
+        Ok(())
+    }
+
+    fn visit_trivia_stmt(&mut self, _trivia: &Trivia) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<(Token, Vec<Stmt>)>,
+        finally: &Option<Vec<Stmt>>,
+    ) -> Result<(), Error> {
+        self.begin_scope();
+        self.resolve_stmts(body);
+        self.end_scope();
+
+        // The caught value shares its scope with the catch body.
+        if let Some((name, catch_body)) = catch {
+            self.begin_scope();
+            self.declare(name, LocalKind::Other);
+            self.define(name);
+            self.resolve_stmts(catch_body);
+            self.end_scope();
+        }
+
+        if let Some(finally_body) = finally {
+            self.begin_scope();
+            self.resolve_stmts(finally_body);
+            self.end_scope();
+        }
+        Ok(())
+    }
+
     fn visit_var_stmt(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<(), Error> {
-        self.declare(name);
+        self.declare(name, LocalKind::Variable);
         if let Some(init) = initializer {
             self.resolve_expr(init);
         }
@@ -349,9 +842,144 @@ impl<'i> stmt::Visitor<()> for Resolver<'i> {
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> Result<(), Error> {
+    fn visit_while_stmt(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+    ) -> Result<(), Error> {
         self.resolve_expr(condition);
+        let enclosing_loop = mem::replace(&mut self.in_loop, true);
         self.resolve_stmt(body);
+        self.in_loop = enclosing_loop;
+        if let Some(inc) = increment {
+            self.resolve_expr(inc);
+        }
         Ok(())
     }
 }
+
+/// The number of arguments calling a class takes: that of its `init` method, or else of the
+/// initializer it inherits.
+fn class_arity(methods: &[Stmt], has_superclass: bool, inherited: Option<usize>) -> Option<usize> {
+    let init = methods.iter().find_map(|method| match method {
+        Stmt::Function {
+            name,
+            params,
+            kind: FunctionKind::Method,
+            ..
+        } if name.lexeme == "init" => Some(params.len()),
+        _ => None,
+    });
+    match init {
+        Some(arity) => Some(arity),
+        None if has_superclass => inherited,
+        None => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    /// Returns the messages of the warnings and errors with their lines.
+    fn lint(source: &str, lints: &[Lint]) -> Vec<(usize, String)> {
+        let mut scanner = Scanner::new(source.to_string());
+        let statements = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        let (_, diagnostics) = error::collect(|| {
+            Resolver::with_lints(&mut interpreter, lints).resolve_stmts(&statements)
+        });
+        diagnostics
+            .into_iter()
+            .map(|d| {
+                let start = d.primary.map_or(0, |label| label.span.start);
+                (source[..start].matches('\n').count() + 1, d.message)
+            })
+            .collect()
+    }
+
+    const SOURCE: &str = "\
+fun f(a, b, _c) {
+  var unused = a;
+  unused = 2;
+  return 1;
+  print a;
+}
+class P < Q { init(x) {} }
+class Q { init(x, y) { var f = x + y; print f; } }
+f(1, 2);
+P(1, 2);
+Q(1);
+undeclared = 1;
+";
+
+    #[test]
+    fn every_lint() {
+        assert_eq!(
+            lint(SOURCE, &Lint::ALL),
+            vec![
+                (
+                    5,
+                    "Unreachable code after 'return'. [unreachable-code]".to_string()
+                ),
+                (1, "Unused parameter 'b'. [unused-parameter]".to_string()),
+                (2, "Unused variable 'unused'. [unused-variable]".to_string()),
+                (7, "Unused parameter 'x'. [unused-parameter]".to_string()),
+                (
+                    8,
+                    "'f' shadows a variable of an outer scope. [shadowing]".to_string()
+                ),
+                (
+                    9,
+                    "Expected 3 arguments but got 2. [argument-count]".to_string()
+                ),
+                (
+                    10,
+                    "Expected 1 arguments but got 2. [argument-count]".to_string()
+                ),
+                (
+                    11,
+                    "Expected 2 arguments but got 1. [argument-count]".to_string()
+                ),
+                (
+                    12,
+                    "Assignment to undeclared variable 'undeclared'. [undeclared-assignment]"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unreachable_code_points_at_the_dead_statement() {
+        let source = "fun f(a) {\n  return a;\n  print a + 1;\n}";
+        let mut scanner = Scanner::new(source.to_string());
+        let statements = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        let (_, diagnostics) = error::collect(|| {
+            Resolver::with_lints(&mut interpreter, &[Lint::UnreachableCode])
+                .resolve_stmts(&statements)
+        });
+
+        let text = |span: Span| &source[span.start..span.end];
+        let primary = diagnostics[0].primary.as_ref().unwrap();
+        assert_eq!(text(primary.span), "print a + 1");
+        let secondary = &diagnostics[0].secondary[0];
+        assert_eq!(text(secondary.span), "return");
+        assert_eq!(secondary.message, "any code after this is unreachable");
+    }
+
+    #[test]
+    fn lints_are_off_by_default() {
+        assert!(lint(SOURCE, &[]).is_empty());
+        assert_eq!(lint(SOURCE, &[Lint::UnusedVariable]).len(), 1);
+
+        // A plain import can define any global.
+        let source = "import \"lib.lox\";\nx = 1;";
+        assert!(lint(source, &[Lint::UndeclaredAssignment]).is_empty());
+    }
+}
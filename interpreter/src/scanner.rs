@@ -1,32 +1,59 @@
-use crate::error::error;
-use crate::token::{Token, TokenType, KEYWORDS};
+use crate::error::{current_source, error};
+use crate::token::{Comment, SourceId, Span, Token, TokenType, KEYWORDS};
 
+/// Scans the source in a single pass.
+///
+/// All positions are byte offsets into the source, so slicing it always falls on character
+/// boundaries.
 pub struct Scanner {
     source: String,
     tokens: Vec<Token>,
+    pub comments: Vec<Comment>,
+
+    /// The source that the spans of the tokens point into.
+    source_id: SourceId,
     start: usize,
     current: usize,
     line: i32,
+    column: usize, // Of the next character.
+
+    // Where the token being scanned starts.
+    start_line: i32,
+    start_column: usize,
+
+    pub had_error: bool,
 }
 
 impl Scanner {
+    /// Scans a source whose spans point into the current source of the error reporting.
     pub fn new(source: String) -> Self {
         Self {
             source,
             tokens: Vec::new(),
+            comments: Vec::new(),
+            source_id: current_source(),
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            had_error: false,
         }
     }
 
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
         while !self.is_at_end() {
             self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
             self.scan_token();
         }
 
-        self.tokens.push(Token::new(TokenType::EOF, "", self.line));
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.add_token(TokenType::EOF);
         &self.tokens
     }
 
@@ -37,6 +64,9 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
+            ':' => self.add_token(TokenType::Colon),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
@@ -77,20 +107,24 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.comments.push(Comment {
+                        text: self.source[self.start..self.current].trim_end().to_string(),
+                        line: self.start_line,
+                        span: self.span(),
+                    });
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            ' ' | '\r' | '\t' => (), // Ignore whitespace
-            '\n' => self.line += 1,
+            ' ' | '\r' | '\t' | '\n' => (), // Ignore whitespace, `advance` counts lines.
             '"' => self.string(),
             c => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.number()
                 } else if c.is_alphabetic() || c == '_' {
                     self.identifier()
                 } else {
-                    error(self.line, "Unexpected character.")
+                    self.error("Unexpected character.")
                 }
             }
         }
@@ -112,16 +146,16 @@ impl Scanner {
     }
 
     fn number(&mut self) {
-        while self.peek().is_digit(10) {
+        while self.peek().is_ascii_digit() {
             self.advance();
         }
 
         // Look for a fractional part.
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // Consumer the ".".
             self.advance();
 
-            while self.peek().is_digit(10) {
+            while self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
@@ -137,15 +171,13 @@ impl Scanner {
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
             self.advance();
         }
 
         // Unterminated string.
         if self.is_at_end() {
-            error(self.line, "Unterminated string.");
+            self.error("Unterminated string.");
+            return;
         }
 
         // The closing ".
@@ -161,30 +193,20 @@ impl Scanner {
     }
 
     fn r#match(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        // TODO: !self.source.get(self.current..self.current).contains(expected)
-        if self
-            .source
-            .chars()
-            .nth(self.current)
-            .expect("Unexpected end of source.")
-            != expected
-        {
+        if self.peek() != expected || self.is_at_end() {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn peek(&self) -> char {
-        self.source.chars().nth(self.current).unwrap_or('\0')
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        self.source.chars().nth(self.current + 1).unwrap_or('\0')
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn is_at_end(&self) -> bool {
@@ -192,10 +214,15 @@ impl Scanner {
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
-        // TODO: work on &str directly.
-        let char_vec: Vec<char> = self.source.chars().collect();
-        char_vec[self.current - 1]
+        let c = self.peek();
+        self.current += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        c
     }
 
     fn add_token(&mut self, tpe: TokenType) {
@@ -203,6 +230,54 @@ impl Scanner {
             .source
             .get(self.start..self.current)
             .expect("Source token is empty.");
-        self.tokens.push(Token::new(tpe, text, self.line))
+        self.tokens.push(Token {
+            tpe,
+            lexeme: text.to_string(),
+            line: self.start_line,
+            column: self.start_column,
+            span: self.span(),
+        })
+    }
+
+    fn error(&mut self, message: &str) {
+        error(self.span(), message);
+        self.had_error = true;
+    }
+
+    /// The bytes of the current lexeme.
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            source: self.source_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_and_columns() {
+        let mut scanner = Scanner::new("var größe = \"ä\nö\";\n  größe;".to_string());
+        let tokens: Vec<(usize, i32, usize, &str)> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|t| (t.span.start, t.line, t.column, t.lexeme.as_str()))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, 1, 1, "var"),
+                (4, 1, 5, "größe"),
+                (12, 1, 11, "="),
+                (14, 1, 13, "\"ä\nö\""),
+                (21, 2, 3, ";"),
+                (25, 3, 3, "größe"),
+                (32, 3, 8, ";"),
+                (33, 3, 9, ""),
+            ]
+        );
     }
 }
@@ -0,0 +1,383 @@
+use crate::error::Error;
+use crate::interpreter::Interpreter;
+use crate::object::{Key, LoxMap, Object};
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Defines all native functions in the global environment.
+pub fn define(interpreter: &mut Interpreter) {
+    native(interpreter, "clock", 0, clock);
+
+    // Strings
+    native(interpreter, "len", 1, len);
+    native(interpreter, "substr", 3, substr);
+    native(interpreter, "indexOf", 2, index_of);
+    native(interpreter, "upper", 1, upper);
+    native(interpreter, "split", 2, split);
+
+    // Lists
+    native(interpreter, "push", 2, push);
+    native(interpreter, "pop", 1, pop);
+    native(interpreter, "insert", 3, insert);
+
+    // Lists and maps
+    native(interpreter, "remove", 2, remove);
+
+    // Maps
+    native(interpreter, "keys", 1, keys);
+    native(interpreter, "values", 1, values);
+    native(interpreter, "has", 2, has);
+
+    // Numbers
+    native(interpreter, "floor", 1, floor);
+    native(interpreter, "sqrt", 1, sqrt);
+    native(interpreter, "pow", 2, pow);
+    native(interpreter, "toString", 1, to_string);
+    native(interpreter, "parseNumber", 1, parse_number);
+
+    native(interpreter, "type", 1, type_of);
+
+    // IO
+    native(interpreter, "input", 0, input);
+    native(interpreter, "readFile", 1, read_file);
+    native(interpreter, "writeFile", 2, write_file);
+}
+
+/// Defines `args()`, which returns the arguments given to the script as a list of strings.
+pub fn define_args(interpreter: &mut Interpreter, arguments: &[String]) {
+    let arguments: Vec<Object> = arguments.iter().cloned().map(Object::String).collect();
+    interpreter.define_native("args", 0, move |_, _| {
+        Ok(Object::List(Rc::new(RefCell::new(arguments.clone()))))
+    });
+}
+
+/// Defines a native that does not need the interpreter.
+fn native(
+    interpreter: &mut Interpreter,
+    name: &str,
+    arity: usize,
+    body: fn(&[Object]) -> Result<Object, Error>,
+) {
+    interpreter.define_native(name, arity, move |_, arguments| body(arguments));
+}
+
+fn error<R>(message: String) -> Result<R, Error> {
+    Err(Error::Native { message })
+}
+
+fn string_arg<'a>(function: &str, arguments: &'a [Object], index: usize) -> Result<&'a str, Error> {
+    match &arguments[index] {
+        Object::String(s) => Ok(s),
+        _ => error(format!(
+            "Argument {} of '{}' must be a string.",
+            index + 1,
+            function
+        )),
+    }
+}
+
+fn number_arg(function: &str, arguments: &[Object], index: usize) -> Result<f64, Error> {
+    match arguments[index] {
+        Object::Number(n) => Ok(n),
+        _ => error(format!(
+            "Argument {} of '{}' must be a number.",
+            index + 1,
+            function
+        )),
+    }
+}
+
+fn list_arg<'a>(
+    function: &str,
+    arguments: &'a [Object],
+    index: usize,
+) -> Result<&'a Rc<RefCell<Vec<Object>>>, Error> {
+    match &arguments[index] {
+        Object::List(list) => Ok(list),
+        _ => error(format!(
+            "Argument {} of '{}' must be a list.",
+            index + 1,
+            function
+        )),
+    }
+}
+
+fn map_arg<'a>(
+    function: &str,
+    arguments: &'a [Object],
+    index: usize,
+) -> Result<&'a Rc<RefCell<LoxMap>>, Error> {
+    match &arguments[index] {
+        Object::Map(map) => Ok(map),
+        _ => error(format!(
+            "Argument {} of '{}' must be a map.",
+            index + 1,
+            function
+        )),
+    }
+}
+
+fn key_arg(arguments: &[Object], index: usize) -> Result<Key, Error> {
+    Key::new(arguments[index].clone()).or_else(error)
+}
+
+/// Like `number_arg` but the number must also be a non-negative integer.
+fn index_arg(function: &str, arguments: &[Object], index: usize) -> Result<usize, Error> {
+    let n = number_arg(function, arguments, index)?;
+    if n < 0.0 || n.fract() != 0.0 {
+        return error(format!(
+            "Argument {} of '{}' must be a non-negative integer.",
+            index + 1,
+            function
+        ));
+    }
+    Ok(n as usize)
+}
+
+fn clock(_arguments: &[Object]) -> Result<Object, Error> {
+    Ok(Object::Number(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Could not retrieve time.")
+            .as_millis() as f64,
+    ))
+}
+
+/// Strings are indexed by characters, not bytes.
+fn len(arguments: &[Object]) -> Result<Object, Error> {
+    match &arguments[0] {
+        Object::String(s) => Ok(Object::Number(s.chars().count() as f64)),
+        Object::List(list) => Ok(Object::Number(list.borrow().len() as f64)),
+        Object::Map(map) => Ok(Object::Number(map.borrow().len() as f64)),
+        _ => error("Argument 1 of 'len' must be a string, a list or a map.".to_string()),
+    }
+}
+
+/// `substr(string, start, length)` is cut at the end of the string.
+fn substr(arguments: &[Object]) -> Result<Object, Error> {
+    let s = string_arg("substr", arguments, 0)?;
+    let start = index_arg("substr", arguments, 1)?;
+    let length = index_arg("substr", arguments, 2)?;
+    if start > s.chars().count() {
+        return error(format!("Start {} of 'substr' is out of bounds.", start));
+    }
+    Ok(Object::String(s.chars().skip(start).take(length).collect()))
+}
+
+/// Returns the character index of the first occurrence or -1.
+fn index_of(arguments: &[Object]) -> Result<Object, Error> {
+    let s = string_arg("indexOf", arguments, 0)?;
+    let needle = string_arg("indexOf", arguments, 1)?;
+    let index = s
+        .find(needle)
+        .map(|byte| s[..byte].chars().count() as f64)
+        .unwrap_or(-1.0);
+    Ok(Object::Number(index))
+}
+
+fn upper(arguments: &[Object]) -> Result<Object, Error> {
+    let s = string_arg("upper", arguments, 0)?;
+    Ok(Object::String(s.to_uppercase()))
+}
+
+/// Splits into characters if the separator is empty.
+fn split(arguments: &[Object]) -> Result<Object, Error> {
+    let s = string_arg("split", arguments, 0)?;
+    let separator = string_arg("split", arguments, 1)?;
+    let parts: Vec<Object> = if separator.is_empty() {
+        s.chars().map(|c| Object::String(c.to_string())).collect()
+    } else {
+        s.split(separator)
+            .map(|part| Object::String(part.to_string()))
+            .collect()
+    };
+    Ok(Object::List(Rc::new(RefCell::new(parts))))
+}
+
+fn push(arguments: &[Object]) -> Result<Object, Error> {
+    let list = list_arg("push", arguments, 0)?;
+    list.borrow_mut().push(arguments[1].clone());
+    Ok(Object::Null)
+}
+
+fn pop(arguments: &[Object]) -> Result<Object, Error> {
+    let list = list_arg("pop", arguments, 0)?;
+    let value = list.borrow_mut().pop();
+    value.map_or_else(|| error("Cannot pop from an empty list.".to_string()), Ok)
+}
+
+/// `insert(list, index, value)` accepts the length of the list as index to append.
+fn insert(arguments: &[Object]) -> Result<Object, Error> {
+    let list = list_arg("insert", arguments, 0)?;
+    let index = index_arg("insert", arguments, 1)?;
+    let length = list.borrow().len();
+    if index > length {
+        return error(format!(
+            "Index {} is out of range for a list of length {}.",
+            index, length
+        ));
+    }
+    list.borrow_mut().insert(index, arguments[2].clone());
+    Ok(Object::Null)
+}
+
+/// Removes by index from a list or by key from a map and returns the removed value.
+fn remove(arguments: &[Object]) -> Result<Object, Error> {
+    if let Object::Map(map) = &arguments[0] {
+        let key = key_arg(arguments, 1)?;
+        let value = map.borrow_mut().remove(&key);
+        return value.map_or_else(|| error(format!("Undefined key '{}'.", key.object())), Ok);
+    }
+
+    let list = list_arg("remove", arguments, 0)?;
+    let index = index_arg("remove", arguments, 1)?;
+    let length = list.borrow().len();
+    if index >= length {
+        return error(format!(
+            "Index {} is out of range for a list of length {}.",
+            index, length
+        ));
+    }
+    let value = list.borrow_mut().remove(index);
+    Ok(value)
+}
+
+/// Returns the keys of a map in the order they were inserted.
+fn keys(arguments: &[Object]) -> Result<Object, Error> {
+    let map = map_arg("keys", arguments, 0)?;
+    let keys = map
+        .borrow()
+        .keys()
+        .map(|key| key.object().clone())
+        .collect();
+    Ok(Object::List(Rc::new(RefCell::new(keys))))
+}
+
+/// Returns the values of a map in the same order as `keys`.
+fn values(arguments: &[Object]) -> Result<Object, Error> {
+    let map = map_arg("values", arguments, 0)?;
+    let values = map.borrow().values().cloned().collect();
+    Ok(Object::List(Rc::new(RefCell::new(values))))
+}
+
+fn has(arguments: &[Object]) -> Result<Object, Error> {
+    let map = map_arg("has", arguments, 0)?;
+    let key = key_arg(arguments, 1)?;
+    let has = map.borrow().contains_key(&key);
+    Ok(Object::Boolean(has))
+}
+
+fn floor(arguments: &[Object]) -> Result<Object, Error> {
+    let n = number_arg("floor", arguments, 0)?;
+    Ok(Object::Number(n.floor()))
+}
+
+fn sqrt(arguments: &[Object]) -> Result<Object, Error> {
+    let n = number_arg("sqrt", arguments, 0)?;
+    Ok(Object::Number(n.sqrt()))
+}
+
+fn pow(arguments: &[Object]) -> Result<Object, Error> {
+    let base = number_arg("pow", arguments, 0)?;
+    let exponent = number_arg("pow", arguments, 1)?;
+    Ok(Object::Number(base.powf(exponent)))
+}
+
+/// Formats any value the same way `print` does.
+fn to_string(arguments: &[Object]) -> Result<Object, Error> {
+    Ok(Object::String(arguments[0].to_string()))
+}
+
+/// Returns nil if the string is not a number.
+fn parse_number(arguments: &[Object]) -> Result<Object, Error> {
+    let s = string_arg("parseNumber", arguments, 0)?;
+    Ok(s.trim().parse().map(Object::Number).unwrap_or(Object::Null))
+}
+
+fn type_of(arguments: &[Object]) -> Result<Object, Error> {
+    let name = match arguments[0] {
+        Object::Boolean(_) => "boolean",
+        Object::Class(_) => "class",
+        Object::Callable(_) => "function",
+        Object::Instance(_) => "instance",
+        Object::List(_) => "list",
+        Object::Map(_) => "map",
+        Object::Module(_) => "module",
+        Object::Null => "nil",
+        Object::Number(_) => "number",
+        Object::String(_) => "string",
+    };
+    Ok(Object::String(name.to_string()))
+}
+
+/// Reads a line from stdin without its line ending. Returns nil at the end of the input.
+fn input(_arguments: &[Object]) -> Result<Object, Error> {
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) => Ok(Object::Null),
+        Ok(_) => {
+            let trimmed = line.trim_end_matches(&['\r', '\n'][..]).len();
+            line.truncate(trimmed);
+            Ok(Object::String(line))
+        }
+        Err(e) => error(format!("Could not read input: {}.", e)),
+    }
+}
+
+fn read_file(arguments: &[Object]) -> Result<Object, Error> {
+    let path = string_arg("readFile", arguments, 0)?;
+    fs::read_to_string(path)
+        .map(Object::String)
+        .or_else(|e| error(format!("Could not read '{}': {}.", path, e)))
+}
+
+fn write_file(arguments: &[Object]) -> Result<Object, Error> {
+    let path = string_arg("writeFile", arguments, 0)?;
+    let contents = string_arg("writeFile", arguments, 1)?;
+    fs::write(path, contents)
+        .map(|_| Object::Null)
+        .or_else(|e| error(format!("Could not write '{}': {}.", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Object {
+        Object::String(s.to_string())
+    }
+
+    #[test]
+    fn strings_are_indexed_by_chars() {
+        let s = string("größer");
+        assert!(len(std::slice::from_ref(&s))
+            .unwrap()
+            .equals(&Object::Number(6.0)));
+        assert!(
+            substr(&[s.clone(), Object::Number(2.0), Object::Number(10.0)])
+                .unwrap()
+                .equals(&string("ößer"))
+        );
+        assert!(index_of(&[s, string("er")])
+            .unwrap()
+            .equals(&Object::Number(4.0)));
+    }
+
+    #[test]
+    fn bad_arguments_fail() {
+        match len(&[Object::Number(1.0)]) {
+            Err(Error::Native { message }) => {
+                assert_eq!(
+                    message,
+                    "Argument 1 of 'len' must be a string, a list or a map."
+                )
+            }
+            _ => panic!("Expected an error."),
+        }
+        assert!(substr(&[string("abc"), Object::Number(0.5), Object::Number(1.0)]).is_err());
+    }
+}
@@ -1,12 +1,19 @@
 use crate::error::Error;
-use crate::token::Token;
+use crate::token::{Comment, Span, Token};
 use std::fmt;
 
+/// Uniquely identifies an expression that refers to a variable.
+///
+/// The `Resolver` records the scope depth of such an expression under its id. Tokens cannot be
+/// used as keys since the same name can appear on one line in different scopes.
+pub type ExprId = usize;
+
 #[derive(Debug, Clone)]
 pub enum Expr {
     Assign {
         name: Token,
         value: Box<Expr>,
+        id: ExprId,
     },
     Binary {
         left: Box<Expr>,
@@ -25,25 +32,47 @@ pub enum Expr {
     Grouping {
         expression: Box<Expr>,
     },
+    Index {
+        object: Box<Expr>,
+        bracket: Token, // The closing bracket, to report errors.
+        index: Box<Expr>,
+    },
+    List {
+        bracket: Token, // The closing bracket.
+        elements: Vec<Expr>,
+    },
     Literal {
         value: LiteralValue,
+        token: Option<Token>, // None for the condition the parser adds to `for (;;)`.
     },
     Logical {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
     },
+    Map {
+        brace: Token, // The closing brace, to report invalid keys.
+        entries: Vec<(Expr, Expr)>,
+    },
     Set {
         object: Box<Expr>,
         name: Token,
         value: Box<Expr>,
     },
+    SetIndex {
+        object: Box<Expr>,
+        bracket: Token,
+        index: Box<Expr>,
+        value: Box<Expr>,
+    },
     Super {
         keyword: Token,
         method: Token,
+        id: ExprId,
     },
     This {
         keyword: Token,
+        id: ExprId,
     },
     Unary {
         operator: Token,
@@ -51,6 +80,7 @@ pub enum Expr {
     },
     Variable {
         name: Token,
+        id: ExprId,
     },
 }
 
@@ -80,9 +110,9 @@ impl fmt::Display for Expr {
 }
 
 impl Expr {
-    pub fn accept<R>(&self, visitor: &mut expr::Visitor<R>) -> Result<R, Error> {
+    pub fn accept<R>(&self, visitor: &mut dyn expr::Visitor<R>) -> Result<R, Error> {
         match self {
-            Expr::Assign { name, value } => visitor.visit_assign_expr(name, value),
+            Expr::Assign { name, value, id } => visitor.visit_assign_expr(name, value, *id),
             Expr::Binary {
                 left,
                 operator,
@@ -95,32 +125,103 @@ impl Expr {
             } => visitor.visit_call_expr(callee, paren, arguments),
             Expr::Get { object, name } => visitor.visit_get_expr(object, name),
             Expr::Grouping { expression } => visitor.visit_grouping_expr(expression),
-            Expr::Literal { value } => visitor.visit_literal_expr(value),
+            Expr::Index {
+                object,
+                bracket,
+                index,
+            } => visitor.visit_index_expr(object, bracket, index),
+            Expr::List { bracket, elements } => visitor.visit_list_expr(bracket, elements),
+            Expr::Literal { value, .. } => visitor.visit_literal_expr(value),
             Expr::Logical {
                 left,
                 operator,
                 right,
             } => visitor.visit_logical_expr(left, operator, right),
+            Expr::Map { brace, entries } => visitor.visit_map_expr(brace, entries),
             Expr::Set {
                 object,
                 name,
                 value,
             } => visitor.visit_set_expr(object, name, value),
-            Expr::Super { keyword, method } => visitor.visit_super_expr(keyword, method),
-            Expr::This { keyword } => visitor.visit_this_expr(keyword),
+            Expr::SetIndex {
+                object,
+                bracket,
+                index,
+                value,
+            } => visitor.visit_set_index_expr(object, bracket, index, value),
+            Expr::Super {
+                keyword,
+                method,
+                id,
+            } => visitor.visit_super_expr(keyword, method, *id),
+            Expr::This { keyword, id } => visitor.visit_this_expr(keyword, *id),
             Expr::Unary { operator, right } => visitor.visit_unary_expr(operator, right),
-            Expr::Variable { name } => visitor.visit_variable_expr(name),
+            Expr::Variable { name, id } => visitor.visit_variable_expr(name, *id),
         }
     }
 }
 
+impl Expr {
+    /// From the first to the last token the expression keeps. Parentheses and the opening
+    /// bracket of lists are not kept, so the span can be shorter than the source.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expr::Assign { name, value, .. } => join(Some(name.span), value.span()),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                join(left.span(), right.span())
+            }
+            Expr::Call { callee, paren, .. } => join(callee.span(), Some(paren.span)),
+            Expr::Get { object, name } => join(object.span(), Some(name.span)),
+            Expr::Grouping { expression } => expression.span(),
+            Expr::Index {
+                object, bracket, ..
+            } => join(object.span(), Some(bracket.span)),
+            Expr::List { bracket, elements } => {
+                let first = elements.first().and_then(Expr::span);
+                join(first, Some(bracket.span))
+            }
+            Expr::Literal { token, .. } => token.as_ref().map(|token| token.span),
+            Expr::Map { brace, entries } => {
+                let first = entries.first().and_then(|(key, _)| key.span());
+                join(first, Some(brace.span))
+            }
+            Expr::Set { object, value, .. } | Expr::SetIndex { object, value, .. } => {
+                join(object.span(), value.span())
+            }
+            Expr::Super {
+                keyword, method, ..
+            } => join(Some(keyword.span), Some(method.span)),
+            Expr::This { keyword, .. } => Some(keyword.span),
+            Expr::Unary { operator, right } => join(Some(operator.span), right.span()),
+            Expr::Variable { name, .. } => Some(name.span),
+        }
+    }
+}
+
+/// The span from the start of the first to the end of the last.
+fn merge(first: Span, last: Span) -> Span {
+    Span {
+        start: first.start.min(last.start),
+        end: first.end.max(last.end),
+        ..first
+    }
+}
+
+fn join(first: Option<Span>, last: Option<Span>) -> Option<Span> {
+    match (first, last) {
+        (Some(first), Some(last)) => Some(merge(first, last)),
+        (first, last) => first.or(last),
+    }
+}
+
 pub mod expr {
-    use super::{Expr, LiteralValue};
+    use super::{Expr, ExprId, LiteralValue};
     use crate::error::Error;
     use crate::token::Token;
 
     pub trait Visitor<R> {
-        fn visit_assign_expr(&mut self, name: &Token, value: &Expr) -> Result<R, Error>;
+        fn visit_assign_expr(&mut self, name: &Token, value: &Expr, id: ExprId)
+            -> Result<R, Error>;
         fn visit_binary_expr(
             &mut self,
             left: &Expr,
@@ -131,7 +232,7 @@ pub mod expr {
             &mut self,
             callee: &Expr,
             paren: &Token,
-            arguments: &Vec<Expr>,
+            arguments: &[Expr],
         ) -> Result<R, Error>;
         fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<R, Error>;
 
@@ -141,6 +242,13 @@ pub mod expr {
         ///
         /// * `expression` - This is the *inner* expression of the grouping.
         fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<R, Error>;
+        fn visit_index_expr(
+            &mut self,
+            object: &Expr,
+            bracket: &Token,
+            index: &Expr,
+        ) -> Result<R, Error>;
+        fn visit_list_expr(&mut self, bracket: &Token, elements: &[Expr]) -> Result<R, Error>;
         fn visit_literal_expr(&self, value: &LiteralValue) -> Result<R, Error>;
         fn visit_logical_expr(
             &mut self,
@@ -148,106 +256,283 @@ pub mod expr {
             operator: &Token,
             right: &Expr,
         ) -> Result<R, Error>;
+        fn visit_map_expr(&mut self, brace: &Token, entries: &[(Expr, Expr)]) -> Result<R, Error>;
         fn visit_set_expr(&mut self, object: &Expr, name: &Token, value: &Expr)
             -> Result<R, Error>;
-        fn visit_super_expr(&mut self, keyword: &Token, method: &Token) -> Result<R, Error>;
-        fn visit_this_expr(&mut self, keyword: &Token) -> Result<R, Error>;
+        fn visit_set_index_expr(
+            &mut self,
+            object: &Expr,
+            bracket: &Token,
+            index: &Expr,
+            value: &Expr,
+        ) -> Result<R, Error>;
+        fn visit_super_expr(
+            &mut self,
+            keyword: &Token,
+            method: &Token,
+            id: ExprId,
+        ) -> Result<R, Error>;
+        fn visit_this_expr(&mut self, keyword: &Token, id: ExprId) -> Result<R, Error>;
         fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<R, Error>;
-        fn visit_variable_expr(&mut self, name: &Token) -> Result<R, Error>;
+        fn visit_variable_expr(&mut self, name: &Token, id: ExprId) -> Result<R, Error>;
     }
 }
 
-#[derive(Clone)]
+/// Comments and blank lines between declarations. Only the formatter asks the parser for them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trivia {
+    BlankLine,
+    Comment(String),
+
+    /// A comment on the line where the previous statement or `{` ends.
+    TrailingComment(String),
+
+    /// A comment inside the previous statement, e.g. between its parameters or after its
+    /// condition.
+    InnerComment(Comment),
+}
+
+/// Statements that don't keep a keyword token keep the span of their first token as `start`.
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Block {
+        start: Span, // The `{`, or `for` for the block around a `for` loop and its initializer.
         statements: Vec<Stmt>,
     },
+    Break {
+        keyword: Token,
+    },
     Class {
+        start: Span,
         name: Token,
 
         // Assume Expr::Variable
         superclass: Option<Expr>,
 
-        // Assume that all are Stmt::Function
+        // Stmt::Function, Stmt::Var for typed fields, and Stmt::Trivia if the parser keeps it.
         methods: Vec<Stmt>,
     },
+    Continue {
+        keyword: Token,
+    },
+
+    /// A declaration with a syntax error. The parser skipped its tokens.
+    Error,
     Expression {
         expression: Expr,
     },
     Function {
+        start: Span, // `fun`, `class` or `set`, or the name of methods and getters.
         name: Token,
         params: Vec<Token>,
         body: Vec<Stmt>,
+        signature: Signature,
+        kind: FunctionKind,
     },
     If {
+        start: Span,
         condition: Expr,
         else_branch: Box<Option<Stmt>>,
         then_branch: Box<Stmt>,
     },
+    Import {
+        keyword: Token,
+        path: String,
+        name: Option<Token>, // Set for `import "path" as name;`
+    },
     Print {
+        start: Span,
         expression: Expr,
     },
     Return {
         keyword: Token,
         value: Option<Expr>,
     },
+    Throw {
+        keyword: Token,
+        value: Expr,
+    },
+    Trivia {
+        trivia: Trivia,
+    },
+    Try {
+        start: Span,
+        body: Vec<Stmt>,
+        catch: Option<(Token, Vec<Stmt>)>, // The name the caught value is bound to and the body.
+        finally: Option<Vec<Stmt>>,
+    },
     Var {
+        start: Span, // `var`, or the name of a field.
         name: Token,
+        annotation: Option<Token>, // The type after `:`. Fields of a class have no initializer.
         initializer: Option<Expr>,
     },
     While {
+        start: Span, // `while` or `for`.
         condition: Expr,
         body: Box<Stmt>,
+
+        // The increment of a desugared `for` loop. It also runs after a `continue`.
+        increment: Option<Expr>,
+
+        // Whether the loop was written as `for`. Its initializer is in an enclosing block.
+        for_loop: bool,
     },
-    Null, // TODO see how stmt is handled after synchronize
+}
+
+/// Where a function is declared and how it is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FunctionKind {
+    Function,
+    Method,
+
+    /// A method declared with `class`, which is called on the class itself.
+    ClassMethod,
+
+    /// A method without parameter list. Reading its property calls it.
+    Getter,
+
+    /// A method declared with `set`. Assigning its property calls it with the value.
+    Setter,
+}
+
+/// The type annotations of a function, e.g. `fun f(a: Number): String`. Only the type checker
+/// reads them.
+#[derive(Debug, Clone, Default)]
+pub struct Signature {
+    pub params: Vec<Option<Token>>, // One for each parameter.
+    pub returns: Option<Token>,
 }
 
 impl Stmt {
-    pub fn accept<R>(&self, visitor: &mut stmt::Visitor<R>) -> Result<R, Error> {
+    pub fn accept<R>(&self, visitor: &mut dyn stmt::Visitor<R>) -> Result<R, Error> {
         match self {
-            Stmt::Block { statements } => visitor.visit_block_stmt(statements),
+            Stmt::Block { statements, .. } => visitor.visit_block_stmt(statements),
+            Stmt::Break { keyword } => visitor.visit_break_stmt(keyword),
             Stmt::Class {
                 name,
                 superclass,
                 methods,
+                ..
             } => visitor.visit_class_stmt(name, superclass, methods),
+            Stmt::Continue { keyword } => visitor.visit_continue_stmt(keyword),
+            Stmt::Error => visitor.visit_error_stmt(),
             Stmt::Expression { expression } => visitor.visit_expression_stmt(expression),
-            Stmt::Function { name, params, body } => {
-                visitor.visit_function_stmt(name, params, body)
-            }
+            Stmt::Function {
+                name, params, body, ..
+            } => visitor.visit_function_stmt(name, params, body),
             Stmt::If {
                 condition,
                 else_branch,
                 then_branch,
+                ..
             } => visitor.visit_if_stmt(condition, else_branch, then_branch),
-            Stmt::Print { expression } => visitor.visit_print_stmt(expression),
+            Stmt::Import {
+                keyword,
+                path,
+                name,
+            } => visitor.visit_import_stmt(keyword, path, name),
+            Stmt::Print { expression, .. } => visitor.visit_print_stmt(expression),
             Stmt::Return { keyword, value } => visitor.visit_return_stmt(keyword, value),
-            Stmt::Var { name, initializer } => visitor.visit_var_stmt(name, initializer),
-            Stmt::While { condition, body } => visitor.visit_while_stmt(condition, body),
-            Stmt::Null => unimplemented!(),
+            Stmt::Throw { keyword, value } => visitor.visit_throw_stmt(keyword, value),
+            Stmt::Trivia { trivia } => visitor.visit_trivia_stmt(trivia),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => visitor.visit_try_stmt(body, catch, finally),
+            Stmt::Var {
+                name, initializer, ..
+            } => visitor.visit_var_stmt(name, initializer),
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => visitor.visit_while_stmt(condition, body, increment),
+        }
+    }
+}
+
+impl Stmt {
+    /// From the first token to the last one the statement keeps. Semicolons and closing braces are
+    /// not kept, so the span can end before the statement does.
+    pub fn span(&self) -> Option<Span> {
+        let all = |statements: &[Stmt]| statements.iter().filter_map(Stmt::span).reduce(merge);
+        match self {
+            Stmt::Block { start, statements } => join(Some(*start), all(statements)),
+            Stmt::Break { keyword } | Stmt::Continue { keyword } => Some(keyword.span),
+            Stmt::Class { start, methods, .. } => join(Some(*start), all(methods)),
+            Stmt::Error | Stmt::Trivia { .. } => None,
+            Stmt::Expression { expression } => expression.span(),
+            Stmt::Function { start, body, .. } => join(Some(*start), all(body)),
+            Stmt::If {
+                start,
+                else_branch,
+                then_branch,
+                ..
+            } => {
+                let span = join(Some(*start), then_branch.span());
+                join(span, else_branch.as_ref().as_ref().and_then(Stmt::span))
+            }
+            Stmt::Import { keyword, name, .. } => {
+                join(Some(keyword.span), name.as_ref().map(|name| name.span))
+            }
+            Stmt::Print { start, expression } => join(Some(*start), expression.span()),
+            Stmt::Return { keyword, value } => {
+                join(Some(keyword.span), value.as_ref().and_then(Expr::span))
+            }
+            Stmt::Throw { keyword, value } => join(Some(keyword.span), value.span()),
+            Stmt::Try {
+                start,
+                body,
+                catch,
+                finally,
+            } => {
+                let catch_span = catch
+                    .as_ref()
+                    .and_then(|(name, body)| join(Some(name.span), all(body)));
+                let span = join(join(Some(*start), all(body)), catch_span);
+                join(span, finally.as_deref().and_then(all))
+            }
+            Stmt::Var {
+                start,
+                name,
+                annotation,
+                initializer,
+            } => {
+                let span = join(Some(*start), Some(name.span));
+                let span = join(span, annotation.as_ref().map(|annotation| annotation.span));
+                join(span, initializer.as_ref().and_then(Expr::span))
+            }
+            Stmt::While { start, body, .. } => join(Some(*start), body.span()),
         }
     }
 }
 
 pub mod stmt {
-    use super::{Expr, Stmt};
+    use super::{Expr, Stmt, Trivia};
     use crate::error::Error;
     use crate::token::Token;
 
     pub trait Visitor<R> {
-        fn visit_block_stmt(&mut self, statements: &Vec<Stmt>) -> Result<R, Error>;
+        fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<R, Error>;
+        fn visit_break_stmt(&mut self, keyword: &Token) -> Result<R, Error>;
         fn visit_class_stmt(
             &mut self,
             name: &Token,
             superclass: &Option<Expr>,
-            methods: &Vec<Stmt>,
+            methods: &[Stmt],
         ) -> Result<R, Error>;
+        fn visit_continue_stmt(&mut self, keyword: &Token) -> Result<R, Error>;
+        fn visit_error_stmt(&mut self) -> Result<R, Error>;
         fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<R, Error>;
         fn visit_function_stmt(
             &mut self,
             name: &Token,
-            params: &Vec<Token>,
-            body: &Vec<Stmt>,
+            params: &[Token],
+            body: &[Stmt],
         ) -> Result<R, Error>;
         fn visit_if_stmt(
             &mut self,
@@ -255,29 +540,75 @@ pub mod stmt {
             else_branch: &Option<Stmt>,
             then_branch: &Stmt,
         ) -> Result<R, Error>;
+        fn visit_import_stmt(
+            &mut self,
+            keyword: &Token,
+            path: &str,
+            name: &Option<Token>,
+        ) -> Result<R, Error>;
         fn visit_print_stmt(&mut self, expression: &Expr) -> Result<R, Error>;
         fn visit_return_stmt(&mut self, keyword: &Token, value: &Option<Expr>) -> Result<R, Error>;
+        fn visit_throw_stmt(&mut self, keyword: &Token, value: &Expr) -> Result<R, Error>;
+        fn visit_trivia_stmt(&mut self, trivia: &Trivia) -> Result<R, Error>;
+        fn visit_try_stmt(
+            &mut self,
+            body: &[Stmt],
+            catch: &Option<(Token, Vec<Stmt>)>,
+            finally: &Option<Vec<Stmt>>,
+        ) -> Result<R, Error>;
         fn visit_var_stmt(&mut self, name: &Token, initializer: &Option<Expr>) -> Result<R, Error>;
-        fn visit_while_stmt(&mut self, condition: &Expr, body: &Stmt) -> Result<R, Error>;
+        fn visit_while_stmt(
+            &mut self,
+            condition: &Expr,
+            body: &Stmt,
+            increment: &Option<Expr>,
+        ) -> Result<R, Error>;
     }
 }
 
+/// Prints the syntax tree as S-expressions, one line per top-level statement.
 pub struct AstPrinter;
 
 impl AstPrinter {
+    #[allow(dead_code)] // Only used by tests.
     pub fn print(&mut self, expr: Expr) -> Result<String, Error> {
         expr.accept(self)
     }
 
+    pub fn print_statements(&mut self, statements: &[Stmt]) -> Result<String, Error> {
+        let lines = statements
+            .iter()
+            .map(|statement| statement.accept(self))
+            .collect::<Result<Vec<String>, Error>>()?;
+        Ok(lines.join("\n"))
+    }
+
+    /// Like `parenthesize` but for statements, e.g. the body of a block.
+    fn parenthesize_statements(
+        &mut self,
+        name: String,
+        statements: &[Stmt],
+    ) -> Result<String, Error> {
+        let mut r = String::new();
+        r.push('(');
+        r.push_str(&name);
+        for statement in statements {
+            r.push(' ');
+            r.push_str(&statement.accept(self)?);
+        }
+        r.push(')');
+        Ok(r)
+    }
+
     fn parenthesize(&mut self, name: String, exprs: Vec<&Expr>) -> Result<String, Error> {
         let mut r = String::new();
-        r.push_str("(");
+        r.push('(');
         r.push_str(&name);
         for e in exprs {
-            r.push_str(" ");
+            r.push(' ');
             r.push_str(&e.accept(self)?);
         }
-        r.push_str(")");
+        r.push(')');
         Ok(r)
     }
 }
@@ -300,6 +631,19 @@ impl expr::Visitor<String> for AstPrinter {
         self.parenthesize("group".to_string(), vec![expr])
     }
 
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+    ) -> Result<String, Error> {
+        self.parenthesize("[]".to_string(), vec![object, index])
+    }
+
+    fn visit_list_expr(&mut self, _bracket: &Token, elements: &[Expr]) -> Result<String, Error> {
+        self.parenthesize("list".to_string(), elements.iter().collect())
+    }
+
     fn visit_literal_expr(&self, value: &LiteralValue) -> Result<String, Error> {
         Ok(value.to_string()) // check for null
     }
@@ -313,6 +657,18 @@ impl expr::Visitor<String> for AstPrinter {
         self.parenthesize(operator.lexeme.clone(), vec![left, right])
     }
 
+    fn visit_map_expr(
+        &mut self,
+        _brace: &Token,
+        entries: &[(Expr, Expr)],
+    ) -> Result<String, Error> {
+        let exprs = entries
+            .iter()
+            .flat_map(|(key, value)| vec![key, value])
+            .collect();
+        self.parenthesize("map".to_string(), exprs)
+    }
+
     fn visit_set_expr(
         &mut self,
         object: &Expr,
@@ -322,11 +678,26 @@ impl expr::Visitor<String> for AstPrinter {
         self.parenthesize(name.lexeme.clone(), vec![object, value])
     }
 
-    fn visit_super_expr(&mut self, _keyword: &Token, _method: &Token) -> Result<String, Error> {
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        _bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<String, Error> {
+        self.parenthesize("[]=".to_string(), vec![object, index, value])
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        _keyword: &Token,
+        _method: &Token,
+        _id: ExprId,
+    ) -> Result<String, Error> {
         Ok("super".to_string())
     }
 
-    fn visit_this_expr(&mut self, _keyword: &Token) -> Result<String, Error> {
+    fn visit_this_expr(&mut self, _keyword: &Token, _id: ExprId) -> Result<String, Error> {
         Ok("this".to_string())
     }
 
@@ -334,27 +705,206 @@ impl expr::Visitor<String> for AstPrinter {
         self.parenthesize(operator.lexeme.clone(), vec![right])
     }
 
-    fn visit_variable_expr(&mut self, name: &Token) -> Result<String, Error> {
+    fn visit_variable_expr(&mut self, name: &Token, _id: ExprId) -> Result<String, Error> {
         Ok(name.lexeme.clone())
     }
 
-    fn visit_assign_expr(&mut self, name: &Token, value: &Expr) -> Result<String, Error> {
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _id: ExprId,
+    ) -> Result<String, Error> {
         self.parenthesize(name.lexeme.clone(), vec![value])
     }
 
     fn visit_call_expr(
         &mut self,
-        _callee: &Expr,
+        callee: &Expr,
         _paren: &Token,
-        _arguments: &Vec<Expr>,
+        arguments: &[Expr],
+    ) -> Result<String, Error> {
+        let exprs = std::iter::once(callee).chain(arguments).collect();
+        self.parenthesize("call".to_string(), exprs)
+    }
+}
+
+impl stmt::Visitor<String> for AstPrinter {
+    fn visit_block_stmt(&mut self, statements: &[Stmt]) -> Result<String, Error> {
+        self.parenthesize_statements("block".to_string(), statements)
+    }
+
+    fn visit_break_stmt(&mut self, _keyword: &Token) -> Result<String, Error> {
+        Ok("(break)".to_string())
+    }
+
+    fn visit_class_stmt(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Expr>,
+        methods: &[Stmt],
+    ) -> Result<String, Error> {
+        let mut header = format!("class {}", name.lexeme);
+        if let Some(superclass) = superclass {
+            header.push_str(&format!(" (< {})", superclass.accept(self)?));
+        }
+
+        // Methods other than plain ones are wrapped, e.g. `(get (fun area () ...))`.
+        let mut r = format!("({}", header);
+        for method in methods {
+            let printed = method.accept(self)?;
+            let printed = match method {
+                Stmt::Function {
+                    kind: FunctionKind::ClassMethod,
+                    ..
+                } => format!("(class {})", printed),
+                Stmt::Function {
+                    kind: FunctionKind::Getter,
+                    ..
+                } => format!("(get {})", printed),
+                Stmt::Function {
+                    kind: FunctionKind::Setter,
+                    ..
+                } => format!("(set {})", printed),
+                _ => printed,
+            };
+            r.push(' ');
+            r.push_str(&printed);
+        }
+        r.push(')');
+        Ok(r)
+    }
+
+    fn visit_continue_stmt(&mut self, _keyword: &Token) -> Result<String, Error> {
+        Ok("(continue)".to_string())
+    }
+
+    fn visit_error_stmt(&mut self) -> Result<String, Error> {
+        Ok("(error)".to_string())
+    }
+
+    fn visit_expression_stmt(&mut self, expression: &Expr) -> Result<String, Error> {
+        self.parenthesize(";".to_string(), vec![expression])
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Stmt],
+    ) -> Result<String, Error> {
+        let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+        let header = format!("fun {} ({})", name.lexeme, params.join(" "));
+        self.parenthesize_statements(header, body)
+    }
+
+    fn visit_if_stmt(
+        &mut self,
+        condition: &Expr,
+        else_branch: &Option<Stmt>,
+        then_branch: &Stmt,
+    ) -> Result<String, Error> {
+        let mut r = format!(
+            "(if {} {}",
+            condition.accept(self)?,
+            then_branch.accept(self)?
+        );
+        if let Some(else_branch) = else_branch {
+            r.push(' ');
+            r.push_str(&else_branch.accept(self)?);
+        }
+        r.push(')');
+        Ok(r)
+    }
+
+    fn visit_import_stmt(
+        &mut self,
+        _keyword: &Token,
+        path: &str,
+        name: &Option<Token>,
+    ) -> Result<String, Error> {
+        Ok(match name {
+            Some(name) => format!("(import \"{}\" as {})", path, name.lexeme),
+            None => format!("(import \"{}\")", path),
+        })
+    }
+
+    fn visit_print_stmt(&mut self, expression: &Expr) -> Result<String, Error> {
+        self.parenthesize("print".to_string(), vec![expression])
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        _keyword: &Token,
+        value: &Option<Expr>,
+    ) -> Result<String, Error> {
+        self.parenthesize("return".to_string(), value.iter().collect())
+    }
+
+    fn visit_throw_stmt(&mut self, _keyword: &Token, value: &Expr) -> Result<String, Error> {
+        self.parenthesize("throw".to_string(), vec![value])
+    }
+
+    fn visit_trivia_stmt(&mut self, trivia: &Trivia) -> Result<String, Error> {
+        Ok(match trivia {
+            Trivia::BlankLine => "(blank)".to_string(),
+            Trivia::Comment(text)
+            | Trivia::TrailingComment(text)
+            | Trivia::InnerComment(Comment { text, .. }) => {
+                format!("(comment {:?})", text)
+            }
+        })
+    }
+
+    fn visit_try_stmt(
+        &mut self,
+        body: &[Stmt],
+        catch: &Option<(Token, Vec<Stmt>)>,
+        finally: &Option<Vec<Stmt>>,
     ) -> Result<String, Error> {
-        unimplemented!()
+        let mut r = String::from("(try ");
+        r.push_str(&self.parenthesize_statements("block".to_string(), body)?);
+        if let Some((name, body)) = catch {
+            r.push(' ');
+            r.push_str(&self.parenthesize_statements(format!("catch {}", name.lexeme), body)?);
+        }
+        if let Some(body) = finally {
+            r.push(' ');
+            r.push_str(&self.parenthesize_statements("finally".to_string(), body)?);
+        }
+        r.push(')');
+        Ok(r)
+    }
+
+    fn visit_var_stmt(
+        &mut self,
+        name: &Token,
+        initializer: &Option<Expr>,
+    ) -> Result<String, Error> {
+        self.parenthesize(format!("var {}", name.lexeme), initializer.iter().collect())
+    }
+
+    fn visit_while_stmt(
+        &mut self,
+        condition: &Expr,
+        body: &Stmt,
+        increment: &Option<Expr>,
+    ) -> Result<String, Error> {
+        let mut r = format!("(while {} {}", condition.accept(self)?, body.accept(self)?);
+        if let Some(increment) = increment {
+            r.push(' ');
+            r.push_str(&increment.accept(self)?);
+        }
+        r.push(')');
+        Ok(r)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
     use crate::token::{Token, TokenType};
 
     #[test]
@@ -364,12 +914,18 @@ mod tests {
                 operator: Token::new(TokenType::Minus, "-", 1),
                 right: Box::new(Expr::Literal {
                     value: LiteralValue::Number(123f64),
+                    token: Some(Token::new(TokenType::Number { literal: 123f64 }, "123", 1)),
                 }),
             }),
             operator: Token::new(TokenType::Star, "*", 1),
             right: Box::new(Expr::Grouping {
                 expression: Box::new(Expr::Literal {
                     value: LiteralValue::Number(45.67f64),
+                    token: Some(Token::new(
+                        TokenType::Number { literal: 45.67f64 },
+                        "45.67",
+                        1,
+                    )),
                 }),
             }),
         };
@@ -380,4 +936,37 @@ mod tests {
             "(* (- 123) (group 45.67))"
         );
     }
+
+    #[test]
+    fn print_statements() {
+        let source =
+            "class A < B { f(x) { return x; } }\nfor (var i = 0; i < 2; i = i + 1) print f(i);";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        assert_eq!(
+            AstPrinter.print_statements(&statements).unwrap(),
+            "(class A (< B) (fun f (x) (return x)))\n\
+             (block (var i 0) (while (< i 2) (print (call f i)) (i (+ i 1))))"
+        );
+    }
+
+    #[test]
+    fn statement_spans_start_at_their_keyword() {
+        let source =
+            "var a = 1;\nif (a) print a; else { print 2; }\nfor (;;) break;\nwhile (a) a = nil;";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+
+        let text = |statement: &Stmt| {
+            let span = statement.span().unwrap();
+            &source[span.start..span.end]
+        };
+        assert_eq!(text(&statements[0]), "var a = 1");
+        assert_eq!(text(&statements[1]), "if (a) print a; else { print 2");
+        assert_eq!(text(&statements[2]), "for (;;) break");
+        assert_eq!(text(&statements[3]), "while (a) a = nil");
+    }
 }
@@ -1,7 +1,9 @@
 use std::fmt;
-use std::hash::{Hash, Hasher};
 extern crate phf;
 
+pub use diagnostics::{SourceId, Span};
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Single-character tokens.
@@ -9,6 +11,9 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Comma,
     Dot,
     Minus,
@@ -35,19 +40,26 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Break,
+    Catch,
     Class,
+    Continue,
     Else,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
@@ -62,18 +74,33 @@ pub struct Token {
     pub tpe: TokenType,
     pub lexeme: String,
     pub line: i32,
+
+    /// The column of the first character, counted in characters and starting at 1.
+    pub column: usize,
+    pub span: Span,
 }
 
 impl Token {
+    /// Creates a token that does not come from the source, so it has no column or span.
     pub fn new(tpe: TokenType, lexeme: &str, line: i32) -> Self {
         Self {
             tpe,
             lexeme: lexeme.to_string(),
             line,
+            column: 0,
+            span: Span::default(),
         }
     }
 }
 
+/// A `//` comment. The scanner keeps them apart from the tokens for the formatter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String, // Including the slashes.
+    pub line: i32,
+    pub span: Span,
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.tpe {
@@ -83,12 +110,3 @@ impl fmt::Display for Token {
         }
     }
 }
-
-impl Hash for Token {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.lexeme.hash(state);
-        self.line.hash(state);
-    }
-}
-
-impl Eq for Token {}
@@ -0,0 +1,1004 @@
+//! Checks type annotations like `var x: Number = 1;` before the program runs. The interpreter
+//! ignores them. Code without annotations stays dynamic: whatever the checker cannot know is
+//! `Any`, which fits every type.
+
+use crate::error::{at, report, Error};
+use crate::syntax::{expr, Expr, ExprId, FunctionKind, LiteralValue, Signature, Stmt};
+use crate::token::{Span, Token, TokenType};
+
+use diagnostics::Diagnostic;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// The type of a value as far as the checker knows it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Bool,
+
+    /// A class itself, which is called to create instances.
+    Class(String),
+
+    /// A function, with its parameter and return types if it is declared in the source.
+    Function(Option<Rc<Callable>>),
+
+    /// An instance of a class.
+    Instance(String),
+    List,
+    Map,
+    Nil,
+    Number,
+    String,
+}
+
+/// The types a function takes and returns. Parameters without annotation are `Any`.
+#[derive(Debug, PartialEq)]
+pub struct Callable {
+    pub params: Vec<Type>,
+    pub returns: Type,
+}
+
+impl Type {
+    /// The type an annotation names, unless it is a class.
+    fn builtin(name: &str) -> Option<Type> {
+        Some(match name {
+            "Any" => Type::Any,
+            "Bool" => Type::Bool,
+            "Function" => Type::Function(None),
+            "List" => Type::List,
+            "Map" => Type::Map,
+            "Nil" => Type::Nil,
+            "Number" => Type::Number,
+            "String" => Type::String,
+            _ => return None,
+        })
+    }
+
+    /// Whether the type can be used where a number is needed.
+    fn is_number(&self) -> bool {
+        std::matches!(self, Type::Number | Type::Any)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Bool => write!(f, "Bool"),
+            Type::Class(name) => write!(f, "class {}", name),
+            Type::Function(_) => write!(f, "Function"),
+            Type::Instance(name) => write!(f, "{}", name),
+            Type::List => write!(f, "List"),
+            Type::Map => write!(f, "Map"),
+            Type::Nil => write!(f, "Nil"),
+            Type::Number => write!(f, "Number"),
+            Type::String => write!(f, "String"),
+        }
+    }
+}
+
+/// A variable of a scope.
+#[derive(Debug, Clone)]
+struct Variable {
+    /// The type of the annotation, or else the one inferred from the initializer.
+    tpe: Type,
+
+    /// Only annotated variables are checked when assigned. Others become `Any` when they are
+    /// assigned a value of another type.
+    annotated: bool,
+}
+
+/// The members of a class that have types.
+#[derive(Debug, Clone, Default)]
+struct Class {
+    superclass: Option<String>,
+
+    /// The annotated fields and the names in their declarations.
+    fields: HashMap<String, (Type, Span)>,
+
+    /// Methods, class methods, getters and setters by their kind and name.
+    methods: HashMap<(FunctionKind, String), (Rc<Callable>, Span)>,
+}
+
+pub struct TypeChecker {
+    /// The innermost scope is last. The first one holds the globals.
+    scopes: Vec<HashMap<String, Variable>>,
+    classes: HashMap<String, Class>,
+    current_class: Option<String>,
+
+    /// Whether the function being checked is a class method, so `this` is the class.
+    in_class_method: bool,
+
+    /// The return type of the function being checked, `None` at the top level.
+    returns: Option<Type>,
+    pub had_error: bool,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            classes: HashMap::new(),
+            current_class: None,
+            in_class_method: false,
+            returns: None,
+            had_error: false,
+        }
+    }
+
+    pub fn check_stmts(&mut self, statements: &[Stmt]) {
+        self.declare(statements);
+        for statement in statements {
+            self.check_stmt(statement);
+        }
+    }
+
+    /// Declares the classes and functions of the statements first, since they can be used
+    /// before their declarations, e.g. in the body of another function.
+    fn declare(&mut self, statements: &[Stmt]) {
+        // Annotations can name classes declared further down.
+        for statement in statements {
+            if let Stmt::Class {
+                name, superclass, ..
+            } = statement
+            {
+                let superclass = match superclass {
+                    Some(Expr::Variable { name, .. }) => Some(name.lexeme.clone()),
+                    _ => None,
+                };
+                let class = Class {
+                    superclass,
+                    ..Default::default()
+                };
+                self.classes.insert(name.lexeme.clone(), class);
+                self.define(name, Type::Class(name.lexeme.clone()), false);
+            }
+        }
+
+        for statement in statements {
+            match statement {
+                Stmt::Class { name, methods, .. } => {
+                    // The class stays known while its members are, since they can name it.
+                    let mut class = self.classes.get(&name.lexeme).cloned().unwrap_or_default();
+                    for member in methods {
+                        match member {
+                            Stmt::Function {
+                                name,
+                                params,
+                                signature,
+                                kind,
+                                ..
+                            } => {
+                                let callable = self.callable(params, signature);
+                                class
+                                    .methods
+                                    .insert((*kind, name.lexeme.clone()), (callable, name.span));
+                            }
+                            Stmt::Var {
+                                name,
+                                annotation: Some(annotation),
+                                ..
+                            } => {
+                                let tpe = self.lookup_type(annotation);
+                                class.fields.insert(name.lexeme.clone(), (tpe, name.span));
+                            }
+                            _ => (),
+                        }
+                    }
+                    self.classes.insert(name.lexeme.clone(), class);
+                }
+                Stmt::Function {
+                    name,
+                    params,
+                    signature,
+                    ..
+                } => {
+                    let callable = self.callable(params, signature);
+                    self.define(name, Type::Function(Some(callable)), false);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn check_stmt(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Block { statements, .. } => self.check_block(statements),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => self.check_class(name, superclass, methods),
+            Stmt::Expression { expression }
+            | Stmt::Print { expression, .. }
+            | Stmt::Throw {
+                value: expression, ..
+            } => {
+                self.check_expr(expression);
+            }
+            Stmt::Function {
+                name,
+                params,
+                body,
+                signature,
+                ..
+            } => {
+                let callable = self.callable(params, signature);
+                self.define(name, Type::Function(Some(callable)), false);
+                self.check_function(params, signature, body);
+            }
+            Stmt::If {
+                condition,
+                else_branch,
+                then_branch,
+                ..
+            } => {
+                self.check_expr(condition);
+                self.check_stmt(then_branch);
+                if let Some(else_branch) = else_branch.as_ref() {
+                    self.check_stmt(else_branch);
+                }
+            }
+            Stmt::Import {
+                name: Some(name), ..
+            } => self.define(name, Type::Any, false),
+            Stmt::Return { keyword, value } => {
+                let value = match value {
+                    Some(value) => self.check_expr(value),
+                    None => Type::Nil,
+                };
+                if let Some(returns) = self.returns.clone() {
+                    if !self.assignable(&value, &returns) {
+                        self.error(
+                            keyword,
+                            &format!(
+                                "Cannot return {} from a function returning {}.",
+                                value, returns
+                            ),
+                        );
+                    }
+                }
+            }
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.check_block(body);
+                if let Some((name, body)) = catch {
+                    self.scopes.push(HashMap::new());
+                    self.define(name, Type::Any, false);
+                    self.check_stmts(body);
+                    self.scopes.pop();
+                }
+                if let Some(body) = finally {
+                    self.check_block(body);
+                }
+            }
+            Stmt::Var {
+                name,
+                annotation,
+                initializer,
+                ..
+            } => {
+                let value = initializer.as_ref().map(|value| self.check_expr(value));
+                match annotation {
+                    Some(annotation) => {
+                        let tpe = self.annotation(Some(annotation));
+                        if let Some(value) = value {
+                            self.check_assignment(name, &value, &tpe);
+                        }
+                        self.define(name, tpe, true);
+                    }
+                    // Nothing can be inferred from `nil`.
+                    None => {
+                        let tpe = match value {
+                            Some(Type::Nil) | None => Type::Any,
+                            Some(value) => value,
+                        };
+                        self.define(name, tpe, false);
+                    }
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.check_expr(condition);
+                self.check_stmt(body);
+                if let Some(increment) = increment {
+                    self.check_expr(increment);
+                }
+            }
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::Error
+            | Stmt::Import { name: None, .. }
+            | Stmt::Trivia { .. } => (),
+        }
+    }
+
+    fn check_block(&mut self, statements: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        self.check_stmts(statements);
+        self.scopes.pop();
+    }
+
+    fn check_class(&mut self, name: &Token, superclass: &Option<Expr>, members: &[Stmt]) {
+        self.define(name, Type::Class(name.lexeme.clone()), false);
+        if let Some(superclass) = superclass {
+            self.check_expr(superclass);
+        }
+
+        let enclosing_class = self.current_class.replace(name.lexeme.clone());
+        let enclosing_class_method = self.in_class_method;
+        for member in members {
+            match member {
+                Stmt::Function {
+                    name: method,
+                    params,
+                    body,
+                    signature,
+                    kind,
+                    ..
+                } => {
+                    self.check_override(&name.lexeme, method, *kind);
+                    self.in_class_method = *kind == FunctionKind::ClassMethod;
+                    self.check_function(params, signature, body);
+                }
+                Stmt::Var {
+                    name: field,
+                    annotation,
+                    ..
+                } => {
+                    let tpe = self.annotation(annotation.as_ref());
+                    self.check_field(&name.lexeme, field, &tpe);
+                }
+                _ => (),
+            }
+        }
+        self.current_class = enclosing_class;
+        self.in_class_method = enclosing_class_method;
+    }
+
+    /// Checks that a method can be called like the method of the same kind it overrides.
+    fn check_override(&mut self, class: &str, method: &Token, kind: FunctionKind) {
+        if method.lexeme == "init" && kind == FunctionKind::Method {
+            return;
+        }
+        let key = (kind, method.lexeme.clone());
+        let own = self
+            .classes
+            .get(class)
+            .and_then(|class| class.methods.get(&key))
+            .map(|(callable, _)| Rc::clone(callable));
+        let superclass = self.classes.get(class).and_then(|c| c.superclass.clone());
+        let overridden =
+            superclass.and_then(|superclass| self.method(&superclass, kind, &method.lexeme));
+
+        if let (Some(own), Some((superclass, (callable, span)))) = (own, overridden) {
+            let matches = own.params.len() == callable.params.len()
+                && callable
+                    .params
+                    .iter()
+                    .zip(&own.params)
+                    .all(|(inherited, param)| self.assignable(inherited, param))
+                && self.assignable(&own.returns, &callable.returns);
+            if !matches {
+                let message = format!(
+                    "Method '{}' does not match the signature of '{}.{}'.",
+                    method.lexeme, superclass, method.lexeme
+                );
+                self.report(at(method, &message).with_secondary(span, "overridden method"));
+            }
+        }
+    }
+
+    /// Checks that a field has the type it has in the superclasses.
+    fn check_field(&mut self, class: &str, field: &Token, tpe: &Type) {
+        let superclass = self.classes.get(class).and_then(|c| c.superclass.clone());
+        let inherited = superclass.and_then(|superclass| {
+            self.member(&superclass, |class| {
+                class.fields.get(&field.lexeme).cloned()
+            })
+        });
+        if let Some((superclass, (inherited, span))) = inherited {
+            if *tpe != inherited {
+                let message = format!(
+                    "Field '{}' is declared as {} in '{}'.",
+                    field.lexeme, inherited, superclass
+                );
+                self.report(at(field, &message).with_secondary(span, "declared here"));
+            }
+        }
+    }
+
+    fn check_function(&mut self, params: &[Token], signature: &Signature, body: &[Stmt]) {
+        self.scopes.push(HashMap::new());
+        for (i, param) in params.iter().enumerate() {
+            let annotation = signature.params.get(i).and_then(Option::as_ref);
+            let tpe = self.annotation(annotation);
+            self.define(param, tpe, annotation.is_some());
+        }
+        let returns = self.annotation(signature.returns.as_ref());
+        let enclosing_returns = self.returns.replace(returns);
+
+        self.check_stmts(body);
+
+        self.returns = enclosing_returns;
+        self.scopes.pop();
+    }
+
+    fn check_expr(&mut self, expression: &Expr) -> Type {
+        expression.accept(self).unwrap_or(Type::Any)
+    }
+
+    fn check_assignment(&mut self, name: &Token, value: &Type, tpe: &Type) {
+        if !self.assignable(value, tpe) {
+            self.error(
+                name,
+                &format!(
+                    "Cannot assign {} to '{}' of type {}.",
+                    value, name.lexeme, tpe
+                ),
+            );
+        }
+    }
+
+    /// Whether a value of type `from` can be used where `to` is expected.
+    fn assignable(&self, from: &Type, to: &Type) -> bool {
+        match (from, to) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::Instance(class), Type::Instance(other))
+            | (Type::Class(class), Type::Class(other)) => self.is_subclass(class, other),
+            (Type::Function(_) | Type::Class(_), Type::Function(_)) => true,
+            _ => from == to,
+        }
+    }
+
+    /// Whether `class` is `other` or inherits from it.
+    fn is_subclass(&self, class: &str, other: &str) -> bool {
+        self.ancestors(class).iter().any(|name| name == other)
+    }
+
+    /// The class and the names of the classes it inherits from, nearest first.
+    fn ancestors(&self, class: &str) -> Vec<String> {
+        let mut ancestors = vec![class.to_string()];
+        while let Some(superclass) = self
+            .classes
+            .get(ancestors.last().expect("Ancestors is empty."))
+            .and_then(|class| class.superclass.clone())
+        {
+            // A class cannot inherit from itself, but the checker might run anyway.
+            if ancestors.contains(&superclass) {
+                break;
+            }
+            ancestors.push(superclass);
+        }
+        ancestors
+    }
+
+    /// Finds a member in the class or the nearest class it inherits it from. Returns the name of
+    /// that class too.
+    fn member<T>(&self, class: &str, find: impl Fn(&Class) -> Option<T>) -> Option<(String, T)> {
+        self.ancestors(class).into_iter().find_map(|name| {
+            let member = self.classes.get(&name).and_then(&find)?;
+            Some((name, member))
+        })
+    }
+
+    /// Finds a method of the kind in the class or the nearest class it inherits it from.
+    fn method(
+        &self,
+        class: &str,
+        kind: FunctionKind,
+        name: &str,
+    ) -> Option<(String, (Rc<Callable>, Span))> {
+        let key = (kind, name.to_string());
+        self.member(class, |class| class.methods.get(&key).cloned())
+    }
+
+    /// The type of a property of an instance, which is read like the interpreter does: getters
+    /// first, then fields and methods. Fields without annotation are `Any`.
+    fn property(&self, class: &str, name: &str) -> Type {
+        if let Some((_, (getter, _))) = self.method(class, FunctionKind::Getter, name) {
+            return getter.returns.clone();
+        }
+        if let Some((_, (tpe, _))) = self.member(class, |class| class.fields.get(name).cloned()) {
+            return tpe;
+        }
+        match self.method(class, FunctionKind::Method, name) {
+            Some((_, (callable, _))) => Type::Function(Some(callable)),
+            None => Type::Any,
+        }
+    }
+
+    /// The type of a class method. Unknown ones are `Any` since a superclass might not be known.
+    fn class_method(&self, class: &str, name: &str) -> Type {
+        match self.method(class, FunctionKind::ClassMethod, name) {
+            Some((_, (callable, _))) => Type::Function(Some(callable)),
+            None => Type::Any,
+        }
+    }
+
+    /// The type an annotation names. Unknown names are reported.
+    fn annotation(&mut self, annotation: Option<&Token>) -> Type {
+        match annotation {
+            Some(annotation) => {
+                let known = Type::builtin(&annotation.lexeme).is_some()
+                    || self.classes.contains_key(&annotation.lexeme);
+                if !known {
+                    self.error(
+                        annotation,
+                        &format!("Unknown type '{}'.", annotation.lexeme),
+                    );
+                }
+                self.lookup_type(annotation)
+            }
+            None => Type::Any,
+        }
+    }
+
+    fn lookup_type(&self, annotation: &Token) -> Type {
+        match Type::builtin(&annotation.lexeme) {
+            Some(tpe) => tpe,
+            None if self.classes.contains_key(&annotation.lexeme) => {
+                Type::Instance(annotation.lexeme.clone())
+            }
+            None => Type::Any,
+        }
+    }
+
+    fn callable(&self, params: &[Token], signature: &Signature) -> Rc<Callable> {
+        let params = (0..params.len())
+            .map(|i| match signature.params.get(i).and_then(Option::as_ref) {
+                Some(annotation) => self.lookup_type(annotation),
+                None => Type::Any,
+            })
+            .collect();
+        let returns = match &signature.returns {
+            Some(annotation) => self.lookup_type(annotation),
+            None => Type::Any,
+        };
+        Rc::new(Callable { params, returns })
+    }
+
+    fn define(&mut self, name: &Token, tpe: Type, annotated: bool) {
+        self.scopes
+            .last_mut()
+            .expect("Scopes is empty.")
+            .insert(name.lexeme.clone(), Variable { tpe, annotated });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.report(at(token, message));
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        report(&diagnostic);
+        self.had_error = true;
+    }
+}
+
+impl expr::Visitor<Type> for TypeChecker {
+    fn visit_assign_expr(
+        &mut self,
+        name: &Token,
+        value: &Expr,
+        _id: ExprId,
+    ) -> Result<Type, Error> {
+        let value = self.check_expr(value);
+        match self.lookup(&name.lexeme).cloned() {
+            Some(variable) if variable.annotated => {
+                self.check_assignment(name, &value, &variable.tpe)
+            }
+            Some(variable) if variable.tpe != value => {
+                if let Some(variable) = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.get_mut(&name.lexeme))
+                {
+                    variable.tpe = Type::Any;
+                }
+            }
+            _ => (),
+        }
+        Ok(value)
+    }
+
+    fn visit_binary_expr(
+        &mut self,
+        left: &Expr,
+        operator: &Token,
+        right: &Expr,
+    ) -> Result<Type, Error> {
+        let left = self.check_expr(left);
+        let right = self.check_expr(right);
+        Ok(match operator.tpe {
+            TokenType::Minus | TokenType::Slash | TokenType::Star => {
+                if !left.is_number() || !right.is_number() {
+                    self.error(operator, "Operands must be numbers.");
+                }
+                Type::Number
+            }
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => {
+                if !left.is_number() || !right.is_number() {
+                    self.error(operator, "Operands must be numbers.");
+                }
+                Type::Bool
+            }
+            TokenType::Plus => match (left, right) {
+                (Type::Any, Type::Any) => Type::Any,
+                (Type::Number, Type::Number | Type::Any) | (Type::Any, Type::Number) => {
+                    Type::Number
+                }
+                (Type::String, Type::String | Type::Any) | (Type::Any, Type::String) => {
+                    Type::String
+                }
+                _ => {
+                    self.error(operator, "Operands must be two numbers or two strings.");
+                    Type::Any
+                }
+            },
+            _ => Type::Bool,
+        })
+    }
+
+    fn visit_call_expr(
+        &mut self,
+        callee: &Expr,
+        paren: &Token,
+        arguments: &[Expr],
+    ) -> Result<Type, Error> {
+        let callee = self.check_expr(callee);
+        let arguments: Vec<Type> = arguments.iter().map(|a| self.check_expr(a)).collect();
+        let (callable, result) = match &callee {
+            Type::Function(Some(callable)) => (Some(Rc::clone(callable)), callable.returns.clone()),
+            Type::Class(class) => {
+                let init = self.method(class, FunctionKind::Method, "init");
+                let init = init.map(|(_, (callable, _))| callable);
+                (init, Type::Instance(class.clone()))
+            }
+            Type::Any | Type::Function(None) => (None, Type::Any),
+            _ => {
+                self.error(paren, "Can only call functions and classes.");
+                return Ok(Type::Any);
+            }
+        };
+
+        if let Some(callable) = callable {
+            for (i, (argument, param)) in arguments.iter().zip(&callable.params).enumerate() {
+                if !self.assignable(argument, param) {
+                    self.error(
+                        paren,
+                        &format!("Argument {} must be {} but is {}.", i + 1, param, argument),
+                    );
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn visit_get_expr(&mut self, object: &Expr, name: &Token) -> Result<Type, Error> {
+        Ok(match self.check_expr(object) {
+            Type::Instance(class) => self.property(&class, &name.lexeme),
+            Type::Class(class) => self.class_method(&class, &name.lexeme),
+            Type::Any => Type::Any,
+            _ => {
+                self.error(name, "Only instances have properties.");
+                Type::Any
+            }
+        })
+    }
+
+    fn visit_grouping_expr(&mut self, expression: &Expr) -> Result<Type, Error> {
+        Ok(self.check_expr(expression))
+    }
+
+    fn visit_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<Type, Error> {
+        let object = self.check_expr(object);
+        let index = self.check_expr(index);
+        match object {
+            Type::List if !index.is_number() => self.error(bracket, "Index must be an integer."),
+            Type::List | Type::Map | Type::Any => (),
+            _ => self.error(bracket, "Only lists and maps can be indexed."),
+        }
+        Ok(Type::Any)
+    }
+
+    fn visit_list_expr(&mut self, _bracket: &Token, elements: &[Expr]) -> Result<Type, Error> {
+        for element in elements {
+            self.check_expr(element);
+        }
+        Ok(Type::List)
+    }
+
+    fn visit_literal_expr(&self, value: &LiteralValue) -> Result<Type, Error> {
+        Ok(match value {
+            LiteralValue::Boolean(_) => Type::Bool,
+            LiteralValue::Null => Type::Nil,
+            LiteralValue::Number(_) => Type::Number,
+            LiteralValue::String(_) => Type::String,
+        })
+    }
+
+    fn visit_logical_expr(
+        &mut self,
+        left: &Expr,
+        _operator: &Token,
+        right: &Expr,
+    ) -> Result<Type, Error> {
+        let left = self.check_expr(left);
+        let right = self.check_expr(right);
+        Ok(if left == right { left } else { Type::Any })
+    }
+
+    fn visit_map_expr(&mut self, _brace: &Token, entries: &[(Expr, Expr)]) -> Result<Type, Error> {
+        for (key, value) in entries {
+            self.check_expr(key);
+            self.check_expr(value);
+        }
+        Ok(Type::Map)
+    }
+
+    fn visit_set_expr(&mut self, object: &Expr, name: &Token, value: &Expr) -> Result<Type, Error> {
+        let object = self.check_expr(object);
+        let value = self.check_expr(value);
+        match object {
+            Type::Instance(class) => {
+                // A setter takes the value instead of the field.
+                let setter = self.method(&class, FunctionKind::Setter, &name.lexeme);
+                let field = match setter {
+                    Some((_, (setter, span))) => setter.params.first().map(|t| (t.clone(), span)),
+                    None => self
+                        .member(&class, |class| class.fields.get(&name.lexeme).cloned())
+                        .map(|(_, field)| field),
+                };
+                if let Some((tpe, _)) = field {
+                    if !self.assignable(&value, &tpe) {
+                        let message = format!(
+                            "Cannot assign {} to field '{}' of type {}.",
+                            value, name.lexeme, tpe
+                        );
+                        self.error(name, &message);
+                    }
+                }
+            }
+            Type::Any => (),
+            _ => self.error(name, "Only instances have fields."),
+        }
+        Ok(value)
+    }
+
+    fn visit_set_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<Type, Error> {
+        self.visit_index_expr(object, bracket, index)?;
+        Ok(self.check_expr(value))
+    }
+
+    fn visit_super_expr(
+        &mut self,
+        _keyword: &Token,
+        method: &Token,
+        _id: ExprId,
+    ) -> Result<Type, Error> {
+        let superclass = self
+            .current_class
+            .as_ref()
+            .and_then(|class| self.classes.get(class))
+            .and_then(|class| class.superclass.clone());
+        Ok(match superclass {
+            Some(superclass) if self.in_class_method => {
+                self.class_method(&superclass, &method.lexeme)
+            }
+            Some(superclass) => self.property(&superclass, &method.lexeme),
+            None => Type::Any,
+        })
+    }
+
+    fn visit_this_expr(&mut self, _keyword: &Token, _id: ExprId) -> Result<Type, Error> {
+        Ok(match &self.current_class {
+            Some(class) if self.in_class_method => Type::Class(class.clone()),
+            Some(class) => Type::Instance(class.clone()),
+            None => Type::Any,
+        })
+    }
+
+    fn visit_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<Type, Error> {
+        let right = self.check_expr(right);
+        Ok(match operator.tpe {
+            TokenType::Minus => {
+                if !right.is_number() {
+                    self.error(operator, "Operand must be a number.");
+                }
+                Type::Number
+            }
+            _ => Type::Bool,
+        })
+    }
+
+    fn visit_variable_expr(&mut self, name: &Token, _id: ExprId) -> Result<Type, Error> {
+        Ok(self
+            .lookup(&name.lexeme)
+            .map_or(Type::Any, |variable| variable.tpe.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    /// Returns the messages of the type errors with their lines.
+    fn check(source: &str) -> Vec<(usize, String)> {
+        let mut scanner = Scanner::new(source.to_string());
+        let statements = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let (_, diagnostics) = error::collect(|| TypeChecker::new().check_stmts(&statements));
+        diagnostics
+            .into_iter()
+            .map(|d| {
+                let start = d.primary.map_or(0, |label| label.span.start);
+                (source[..start].matches('\n').count() + 1, d.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unannotated_code_stays_dynamic() {
+        let source = "\
+var a = 1;
+a = \"one\";
+print a + \"!\";
+fun twice(f, x) { return f(f(x)); }
+var b;
+print b.field;
+";
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn inferred_and_annotated_mismatches() {
+        let source = "\
+var n: Number = \"1\";
+var s = \"a\";
+print s - 1;
+fun greet(name: String): String { return 1; }
+greet(2);
+var count: Number = greet(\"x\");
+print -true;
+var list = [1];
+print list[\"0\"];
+var x: Numbr;
+";
+        assert_eq!(
+            check(source),
+            vec![
+                (1, "Cannot assign String to 'n' of type Number.".to_string()),
+                (3, "Operands must be numbers.".to_string()),
+                (
+                    4,
+                    "Cannot return Number from a function returning String.".to_string()
+                ),
+                (5, "Argument 1 must be String but is Number.".to_string()),
+                (
+                    6,
+                    "Cannot assign String to 'count' of type Number.".to_string()
+                ),
+                (7, "Operand must be a number.".to_string()),
+                (9, "Index must be an integer.".to_string()),
+                (10, "Unknown type 'Numbr'.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn class_members_and_inheritance() {
+        let source = "\
+class Shape {
+  name: String;
+  area(): Number { return 0; }
+  scale(by: Number): Shape { return this; }
+}
+class Square < Shape {
+  name: Number;
+  init(side: Number) { this.side = side; }
+  area(): String { return \"big\"; }
+  scale(by: Number): Square { return this; }
+}
+var s: Shape = Square(2);
+var q: Square = Shape();
+s.name = 1;
+print s.area() + 1;
+Square(\"2\");
+print 1 .len;
+";
+        assert_eq!(
+            check(source),
+            vec![
+                (
+                    7,
+                    "Field 'name' is declared as String in 'Shape'.".to_string()
+                ),
+                (
+                    9,
+                    "Method 'area' does not match the signature of 'Shape.area'.".to_string()
+                ),
+                (13, "Cannot assign Shape to 'q' of type Square.".to_string()),
+                (
+                    14,
+                    "Cannot assign Number to field 'name' of type String.".to_string()
+                ),
+                (16, "Argument 1 must be Number but is String.".to_string()),
+                (17, "Only instances have properties.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn class_methods_getters_and_setters() {
+        let source = "\
+class Circle {
+  radius: Number;
+  area: Number { return this.radius * this.radius; }
+  set diameter(d: Number) { this.radius = d / 2; }
+  class unit(): Circle { return this(); }
+}
+class Ring < Circle {
+  area: String { return \"ring\"; }
+}
+var c = Circle.unit();
+print c.area + \"cm\";
+c.diameter = \"10\";
+print Circle.unknown;
+";
+        assert_eq!(
+            check(source),
+            vec![
+                (
+                    8,
+                    "Method 'area' does not match the signature of 'Circle.area'.".to_string()
+                ),
+                (
+                    11,
+                    "Operands must be two numbers or two strings.".to_string()
+                ),
+                (
+                    12,
+                    "Cannot assign String to field 'diameter' of type Number.".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,744 @@
+use interpreter::error::{self, ParseError};
+use interpreter::function::Function;
+use interpreter::interpreter::Interpreter;
+use interpreter::object::Object;
+use interpreter::parser::Parser;
+use interpreter::resolver::{Lint, Reference, Resolver};
+use interpreter::scanner::Scanner;
+use interpreter::syntax::{Expr, FunctionKind, Stmt};
+use interpreter::token::{Token, TokenType};
+
+use diagnostics::{Diagnostic, Span};
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Class,
+    Function,
+    Method,
+    Module,
+    Parameter,
+    Variable,
+}
+
+impl SymbolKind {
+    /// The number of the kind in the `SymbolKind` enum of the LSP specification.
+    pub fn symbol_kind(self) -> u32 {
+        match self {
+            SymbolKind::Class => 5,
+            SymbolKind::Function => 12,
+            SymbolKind::Method => 6,
+            SymbolKind::Module => 2,
+            SymbolKind::Parameter | SymbolKind::Variable => 13,
+        }
+    }
+
+    /// The number of the kind in the `CompletionItemKind` enum of the LSP specification.
+    pub fn completion_kind(self) -> u32 {
+        match self {
+            SymbolKind::Class => 7,
+            SymbolKind::Function => 3,
+            SymbolKind::Method => 2,
+            SymbolKind::Module => 9,
+            SymbolKind::Parameter | SymbolKind::Variable => 6,
+        }
+    }
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SymbolKind::Class => "class",
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Module => "module",
+            SymbolKind::Parameter => "parameter",
+            SymbolKind::Variable => "variable",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A declared name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+
+    /// The name in the declaration.
+    pub span: Span,
+
+    /// The whole declaration of classes and functions, otherwise the name.
+    pub extent: Span,
+
+    /// How the declaration reads after its kind, e.g. `add(a, b)` or `Cake < Dessert`.
+    pub detail: String,
+
+    /// Where a local can be used. Globals and methods have no scope.
+    pub scope: Option<Span>,
+
+    /// The class or function the symbol is declared in.
+    pub container: Option<usize>,
+}
+
+/// A name completion offers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: SymbolKind,
+    pub detail: String,
+}
+
+/// What the scanner, parser and resolver know about one source.
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: Vec<Symbol>,
+
+    /// Every name that refers to a symbol, including the names in declarations. Names after a `.`
+    /// refer to every method of that name.
+    references: Vec<(Span, usize)>,
+}
+
+impl Analysis {
+    /// Analyses the source and checks every lint. The parser recovers after syntax errors, so the
+    /// declarations around them are still known.
+    pub fn new(source: &str) -> Self {
+        let ((symbols, references), mut diagnostics) = error::collect(|| {
+            let mut scanner = Scanner::new(source.to_string());
+            let tokens = scanner.scan_tokens().clone();
+            let (statements, errors) = Parser::new(&tokens).parse_with_errors();
+            for error in &errors {
+                error::report(&ParseError::diagnostic(error));
+            }
+
+            let mut interpreter = Interpreter::new();
+            let mut resolver = Resolver::with_lints(&mut interpreter, &Lint::ALL);
+            resolver.resolve_stmts(&statements);
+            let resolved = std::mem::take(&mut resolver.references);
+
+            let mut collector = Collector::new(source, &tokens);
+            collector.statements(&statements, None, 0);
+            collector.link(&resolved);
+            (collector.symbols, collector.references)
+        });
+        diagnostics.sort_by_key(|d| d.primary.as_ref().map_or(0, |label| label.span.start));
+
+        Analysis {
+            diagnostics,
+            symbols,
+            references,
+        }
+    }
+
+    /// The symbols the name at the offset refers to.
+    fn symbols_at(&self, offset: usize) -> Vec<usize> {
+        self.references
+            .iter()
+            .filter(|(span, _)| span.start <= offset && offset <= span.end)
+            .map(|(_, symbol)| *symbol)
+            .collect()
+    }
+
+    pub fn definitions(&self, offset: usize) -> Vec<&Symbol> {
+        self.symbols_at(offset)
+            .into_iter()
+            .map(|symbol| &self.symbols[symbol])
+            .collect()
+    }
+
+    /// The names that refer to the same symbols as the name at the offset.
+    pub fn references(&self, offset: usize, include_declaration: bool) -> Vec<Span> {
+        let symbols = self.symbols_at(offset);
+        let mut spans: Vec<Span> = self
+            .references
+            .iter()
+            .filter(|(span, symbol)| {
+                symbols.contains(symbol)
+                    && (include_declaration || self.symbols[*symbol].span != *span)
+            })
+            .map(|(span, _)| *span)
+            .collect();
+        spans.sort_by_key(|span| span.start);
+        spans.dedup();
+        spans
+    }
+
+    /// Markdown with the kind and declaration of the name at the offset.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let symbols = self.definitions(offset);
+        if symbols.is_empty() {
+            return None;
+        }
+        let lines: Vec<String> = symbols
+            .iter()
+            .map(|symbol| format!("{} {}", symbol.kind, symbol.detail))
+            .collect();
+        Some(format!("```lox\n{}\n```", lines.join("\n")))
+    }
+
+    /// The names that can be used at the offset, sorted. Inner declarations shadow outer ones.
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let mut visible: HashMap<&str, &Symbol> = HashMap::new();
+        for symbol in &self.symbols {
+            let in_scope = match symbol.scope {
+                _ if symbol.kind == SymbolKind::Method => false,
+                None => true,
+                Some(scope) => {
+                    symbol.span.end <= offset && scope.start <= offset && offset <= scope.end
+                }
+            };
+            let shadows = |other: &&Symbol| match (symbol.scope, other.scope) {
+                (Some(inner), Some(outer)) => inner.end - inner.start <= outer.end - outer.start,
+                (inner, _) => inner.is_some(),
+            };
+            if in_scope && visible.get(symbol.name.as_str()).is_none_or(shadows) {
+                visible.insert(&symbol.name, symbol);
+            }
+        }
+
+        let mut completions: Vec<Completion> = natives()
+            .into_iter()
+            .filter(|(name, _)| !visible.contains_key(name.as_str()))
+            .map(|(name, arity)| Completion {
+                detail: format!("{}({})", name, vec!["_"; arity].join(", ")),
+                label: name,
+                kind: SymbolKind::Function,
+            })
+            .chain(visible.values().map(|symbol| Completion {
+                label: symbol.name.clone(),
+                kind: symbol.kind,
+                detail: symbol.detail.clone(),
+            }))
+            .collect();
+        completions.sort_by(|a, b| a.label.cmp(&b.label));
+        completions
+    }
+
+    /// The symbols at the top of the outline.
+    pub fn outline(&self) -> Vec<usize> {
+        (0..self.symbols.len())
+            .filter(|&symbol| self.symbols[symbol].container.is_none() && self.in_outline(symbol))
+            .collect()
+    }
+
+    /// The symbols nested in a class or function, in the outline.
+    pub fn children(&self, container: usize) -> Vec<usize> {
+        (0..self.symbols.len())
+            .filter(|&symbol| {
+                self.symbols[symbol].container == Some(container) && self.in_outline(symbol)
+            })
+            .collect()
+    }
+
+    /// Classes, functions, methods and globals are listed in the outline, but not locals.
+    fn in_outline(&self, symbol: usize) -> bool {
+        let symbol = &self.symbols[symbol];
+        match symbol.kind {
+            SymbolKind::Class | SymbolKind::Function | SymbolKind::Method => true,
+            _ => symbol.scope.is_none(),
+        }
+    }
+}
+
+/// The native functions every script can call, with their arity.
+fn natives() -> Vec<(String, usize)> {
+    Interpreter::new()
+        .globals
+        .borrow()
+        .globals()
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            Object::Callable(Function::Native { arity, .. }) => Some((name, arity)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collects the symbols of a syntax tree and links names to them.
+struct Collector<'s> {
+    source: &'s str,
+
+    /// The braces of every block, sorted by the opening brace.
+    blocks: Vec<Span>,
+    symbols: Vec<Symbol>,
+    references: Vec<(Span, usize)>,
+
+    /// Names after a `.`.
+    properties: Vec<Span>,
+}
+
+impl<'s> Collector<'s> {
+    fn new(source: &'s str, tokens: &[Token]) -> Self {
+        let mut blocks = Vec::new();
+        let mut open: Vec<Span> = Vec::new();
+        for token in tokens {
+            match token.tpe {
+                TokenType::LeftBrace => open.push(token.span),
+                TokenType::RightBrace => {
+                    if let Some(start) = open.pop() {
+                        blocks.push(Span::new(start.start, token.span.end));
+                    }
+                }
+                _ => (),
+            }
+        }
+        blocks.sort_by_key(|block| block.start);
+
+        Collector {
+            source,
+            blocks,
+            symbols: Vec::new(),
+            references: Vec::new(),
+            properties: Vec::new(),
+        }
+    }
+
+    /// The innermost block around the offset. Locals outside of braces, like the variable of a
+    /// `for` loop at the top level, can be used until the end of the source.
+    fn enclosing_block(&self, offset: usize) -> Span {
+        self.blocks
+            .iter()
+            .filter(|block| block.start < offset && offset < block.end)
+            .min_by_key(|block| block.end - block.start)
+            .copied()
+            .unwrap_or(Span::new(offset, self.source.len()))
+    }
+
+    /// The first block after the offset, e.g. the body of a function after its name.
+    fn block_after(&self, offset: usize) -> Span {
+        self.blocks
+            .iter()
+            .find(|block| block.start >= offset)
+            .copied()
+            .unwrap_or(Span::new(offset, self.source.len()))
+    }
+
+    fn declare(
+        &mut self,
+        name: &Token,
+        kind: SymbolKind,
+        detail: String,
+        extent: Span,
+        scope: Option<Span>,
+        container: Option<usize>,
+    ) -> usize {
+        self.symbols.push(Symbol {
+            name: name.lexeme.clone(),
+            kind,
+            span: name.span,
+            extent,
+            detail,
+            scope,
+            container,
+        });
+        self.references.push((name.span, self.symbols.len() - 1));
+        self.symbols.len() - 1
+    }
+
+    /// Where a declaration at the depth can be used. Declarations at the top level are global.
+    fn scope(&self, name: &Token, depth: usize) -> Option<Span> {
+        if depth == 0 {
+            None
+        } else {
+            Some(self.enclosing_block(name.span.start))
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt], container: Option<usize>, depth: usize) {
+        for statement in statements {
+            self.statement(statement, container, depth);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt, container: Option<usize>, depth: usize) {
+        match statement {
+            Stmt::Block { statements, .. } => self.statements(statements, container, depth + 1),
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
+                let detail = match superclass {
+                    Some(Expr::Variable {
+                        name: superclass, ..
+                    }) => format!("{} < {}", name.lexeme, superclass.lexeme),
+                    _ => name.lexeme.clone(),
+                };
+                let extent = self.extent(name);
+                let scope = self.scope(name, depth);
+                let class = self.declare(name, SymbolKind::Class, detail, extent, scope, container);
+
+                for method in methods {
+                    if let Stmt::Function {
+                        name,
+                        params,
+                        body,
+                        kind,
+                        ..
+                    } = method
+                    {
+                        let qualified = format!("{}.{}", self.symbols[class].name, name.lexeme);
+                        let detail = match kind {
+                            FunctionKind::ClassMethod => {
+                                format!("class {}", signature(&qualified, params))
+                            }
+                            FunctionKind::Getter => qualified,
+                            FunctionKind::Setter => {
+                                format!("set {}", signature(&qualified, params))
+                            }
+                            _ => signature(&qualified, params),
+                        };
+                        let extent = self.extent(name);
+                        let method = self.declare(
+                            name,
+                            SymbolKind::Method,
+                            detail,
+                            extent,
+                            None,
+                            Some(class),
+                        );
+                        self.function(method, params, body);
+                    }
+                }
+            }
+            Stmt::Expression { expression }
+            | Stmt::Print { expression, .. }
+            | Stmt::Throw {
+                value: expression, ..
+            } => self.expression(expression),
+            Stmt::Function {
+                name, params, body, ..
+            } => {
+                let scope = self.scope(name, depth);
+                let detail = signature(&name.lexeme, params);
+                let extent = self.extent(name);
+                let function =
+                    self.declare(name, SymbolKind::Function, detail, extent, scope, container);
+                self.function(function, params, body);
+            }
+            Stmt::If {
+                condition,
+                else_branch,
+                then_branch,
+                ..
+            } => {
+                self.expression(condition);
+                self.statement(then_branch, container, depth);
+                if let Some(else_branch) = else_branch.as_ref() {
+                    self.statement(else_branch, container, depth);
+                }
+            }
+            Stmt::Import {
+                name: Some(name), ..
+            } => {
+                let scope = self.scope(name, depth);
+                let detail = name.lexeme.clone();
+                self.declare(
+                    name,
+                    SymbolKind::Module,
+                    detail,
+                    name.span,
+                    scope,
+                    container,
+                );
+            }
+            Stmt::Return {
+                value: Some(value), ..
+            } => self.expression(value),
+            Stmt::Try {
+                body,
+                catch,
+                finally,
+                ..
+            } => {
+                self.statements(body, container, depth + 1);
+                if let Some((name, body)) = catch {
+                    let scope = Some(self.block_after(name.span.end));
+                    let detail = name.lexeme.clone();
+                    self.declare(
+                        name,
+                        SymbolKind::Variable,
+                        detail,
+                        name.span,
+                        scope,
+                        container,
+                    );
+                    self.statements(body, container, depth + 1);
+                }
+                if let Some(body) = finally {
+                    self.statements(body, container, depth + 1);
+                }
+            }
+            Stmt::Var {
+                name, initializer, ..
+            } => {
+                let scope = self.scope(name, depth);
+                let detail = name.lexeme.clone();
+                self.declare(
+                    name,
+                    SymbolKind::Variable,
+                    detail,
+                    name.span,
+                    scope,
+                    container,
+                );
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+                ..
+            } => {
+                self.expression(condition);
+                self.statement(body, container, depth);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+            Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::Error
+            | Stmt::Import { name: None, .. }
+            | Stmt::Return { value: None, .. }
+            | Stmt::Trivia { .. } => (),
+        }
+    }
+
+    /// From a class or function name to the end of its body.
+    fn extent(&self, name: &Token) -> Span {
+        Span::new(name.span.start, self.block_after(name.span.end).end)
+    }
+
+    /// Declares the parameters of a function or method and collects its body.
+    fn function(&mut self, function: usize, params: &[Token], body: &[Stmt]) {
+        let block = self.block_after(self.symbols[function].span.end);
+        for param in params {
+            let detail = param.lexeme.clone();
+            self.declare(
+                param,
+                SymbolKind::Parameter,
+                detail,
+                param.span,
+                Some(block),
+                Some(function),
+            );
+        }
+        self.statements(body, Some(function), 1);
+    }
+
+    fn expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Assign { value, .. } => self.expression(value),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call {
+                callee, arguments, ..
+            } => {
+                self.expression(callee);
+                arguments
+                    .iter()
+                    .for_each(|argument| self.expression(argument));
+            }
+            Expr::Get { object, name } => {
+                self.properties.push(name.span);
+                self.expression(object);
+            }
+            Expr::Grouping { expression } => self.expression(expression),
+            Expr::Index { object, index, .. } => {
+                self.expression(object);
+                self.expression(index);
+            }
+            Expr::List { elements, .. } => elements.iter().for_each(|e| self.expression(e)),
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expr::Set {
+                object,
+                name,
+                value,
+            } => {
+                self.properties.push(name.span);
+                self.expression(object);
+                self.expression(value);
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.expression(object);
+                self.expression(index);
+                self.expression(value);
+            }
+            Expr::Super { method, .. } => self.properties.push(method.span),
+            Expr::Unary { right, .. } => self.expression(right),
+            Expr::Literal { .. } | Expr::This { .. } | Expr::Variable { .. } => (),
+        }
+    }
+
+    /// Links the names the resolver saw, and the names after a `.`, to their symbols.
+    fn link(&mut self, resolved: &[Reference]) {
+        let declarations: HashMap<usize, usize> = self
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(i, symbol)| (symbol.span.start, i))
+            .collect();
+
+        for reference in resolved {
+            let symbol = match reference.declared {
+                // `this` and `super` are declared implicitly.
+                Some(declared) if declared.start == declared.end => None,
+                Some(declared) => declarations.get(&declared.start).copied(),
+                None => self.global(reference.name),
+            };
+            if let Some(symbol) = symbol {
+                self.references.push((reference.name, symbol));
+            }
+        }
+
+        for property in std::mem::take(&mut self.properties) {
+            let name = &self.source[property.start..property.end];
+            for (i, symbol) in self.symbols.iter().enumerate() {
+                if symbol.kind == SymbolKind::Method && symbol.name == name {
+                    self.references.push((property, i));
+                }
+            }
+        }
+    }
+
+    /// The global a name refers to: the last declaration before it, or else the first one after.
+    fn global(&self, name: Span) -> Option<usize> {
+        let lexeme = &self.source[name.start..name.end];
+        let globals = self.symbols.iter().enumerate().filter(|(_, symbol)| {
+            symbol.scope.is_none() && symbol.kind != SymbolKind::Method && symbol.name == lexeme
+        });
+        let first = globals.clone().next().map(|(i, _)| i);
+        globals
+            .rev()
+            .find(|(_, symbol)| symbol.span.start <= name.start)
+            .map(|(i, _)| i)
+            .or(first)
+    }
+}
+
+fn signature(name: &str, params: &[Token]) -> String {
+    let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+    format!("{}({})", name, params.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diagnostics::Severity;
+
+    const SOURCE: &str = "\
+class Cake {
+  taste(adjective) {
+    var text = adjective;
+    return text;
+  }
+}
+
+fun serve(cake) {
+  cake.taste(\"good\");
+}
+
+var cake = Cake();
+serve(cake);
+";
+
+    fn offset(needle: &str, nth: usize) -> usize {
+        SOURCE.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn definitions_and_references() {
+        let analysis = Analysis::new(SOURCE);
+
+        // The parameter `cake` shadows the global.
+        let severities: Vec<Severity> = analysis.diagnostics.iter().map(|d| d.severity).collect();
+        assert_eq!(severities, vec![Severity::Warning]);
+
+        // The parameter, not the global of the same name.
+        let definition = analysis.definitions(offset("cake.taste", 0));
+        assert_eq!(definition[0].kind, SymbolKind::Parameter);
+        assert_eq!(definition[0].span.start, offset("cake)", 0));
+
+        // Methods are found by name.
+        let definition = analysis.definitions(offset("taste(\"", 0));
+        assert_eq!(definition[0].detail, "Cake.taste(adjective)");
+
+        let references: Vec<usize> = analysis
+            .references(offset("var cake", 0) + 4, true)
+            .iter()
+            .map(|span| span.start)
+            .collect();
+        assert_eq!(references, vec![offset("cake = ", 0), offset("cake);", 0)]);
+
+        assert_eq!(
+            analysis.hover(offset("Cake()", 0)),
+            Some("```lox\nclass Cake\n```".to_string())
+        );
+    }
+
+    #[test]
+    fn completions_in_scope() {
+        let analysis = Analysis::new(SOURCE);
+        let labels = |offset: usize| -> Vec<String> {
+            analysis
+                .completions(offset)
+                .into_iter()
+                .map(|completion| completion.label)
+                .collect()
+        };
+
+        let in_method = labels(offset("return text", 0));
+        for name in &["Cake", "adjective", "cake", "clock", "serve", "text"] {
+            assert!(in_method.contains(&name.to_string()), "{}", name);
+        }
+        assert!(!in_method.contains(&"taste".to_string()));
+
+        let outside = labels(offset("serve(cake);", 0));
+        assert!(!outside.contains(&"text".to_string()));
+        assert!(!outside.contains(&"adjective".to_string()));
+    }
+
+    #[test]
+    fn outline_and_errors() {
+        let analysis = Analysis::new("class A {\n  m() {}\n}\nvar = 1;\nfun f() { var local; }");
+        let outline: Vec<&str> = analysis
+            .outline()
+            .into_iter()
+            .map(|symbol| analysis.symbols[symbol].name.as_str())
+            .collect();
+        assert_eq!(outline, vec!["A", "f"]);
+        assert_eq!(analysis.symbols[analysis.children(0)[0]].name, "m");
+        assert!(analysis.children(2).is_empty());
+
+        let messages: Vec<&str> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Expect variable name.",
+                "Unused variable 'local'. [unused-variable]"
+            ]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use crate::analysis::Analysis;
+
+use diagnostics::Span;
+use serde_json::{json, Value};
+
+/// An open file and what is known about its code.
+pub struct Document {
+    pub text: String,
+    pub analysis: Analysis,
+
+    /// The byte offset of each line.
+    line_starts: Vec<usize>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Document {
+            analysis: Analysis::new(&text),
+            text,
+            line_starts,
+        }
+    }
+
+    /// Turns an LSP position into a byte offset. Editors count characters in UTF-16 code units.
+    pub fn offset(&self, position: &Value) -> Option<usize> {
+        let line = position.get("line")?.as_u64()? as usize;
+        let character = position.get("character")?.as_u64()? as usize;
+
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.text.len(), |e| e - 1);
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= character {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(end)
+    }
+
+    pub fn position(&self, offset: usize) -> Value {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let character: usize = self.text[start..offset].chars().map(char::len_utf16).sum();
+        json!({ "line": line, "character": character })
+    }
+
+    pub fn range(&self, span: Span) -> Value {
+        json!({ "start": self.position(span.start), "end": self.position(span.end) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_count_utf16() {
+        let document = Document::new("var a = \"😀\";\nprint a;".to_string());
+
+        // The emoji is four bytes but two UTF-16 code units.
+        assert_eq!(document.position(14), json!({ "line": 0, "character": 12 }));
+        assert_eq!(
+            document.offset(&json!({ "line": 0, "character": 12 })),
+            Some(14)
+        );
+        assert_eq!(
+            document.offset(&json!({ "line": 1, "character": 6 })),
+            Some(22)
+        );
+        assert_eq!(document.offset(&json!({ "line": 2, "character": 0 })), None);
+    }
+}
@@ -0,0 +1,25 @@
+//! A language server for Lox. Editors start it and talk to it over stdin and stdout.
+
+mod analysis;
+mod document;
+mod server;
+mod transport;
+
+use server::Server;
+
+use std::io;
+use std::process::exit;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match Server::new().run(stdin.lock(), stdout.lock()) {
+        Ok(true) => (),
+        // The client exited without a shutdown request or the connection broke.
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(74) // EX_IOERR
+        }
+    }
+}
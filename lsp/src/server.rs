@@ -0,0 +1,355 @@
+use crate::document::Document;
+use crate::transport::{read_message, write_message};
+
+use diagnostics::{Diagnostic, Severity};
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Answers the requests of one client about the documents it opened.
+pub struct Server {
+    /// The open documents by their URI.
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server {
+            documents: HashMap::new(),
+            shutdown: false,
+        }
+    }
+
+    /// Handles messages until the client sends `exit`. Returns whether it asked for a shutdown
+    /// first.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+        while let Some(message) = read_message(&mut input)? {
+            if message["method"] == "exit" {
+                return Ok(self.shutdown);
+            }
+            for reply in self.handle(&message) {
+                write_message(&mut output, &reply)?;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the response to a request, or the diagnostics a change to a document produced.
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = match message.get("id") {
+            Some(id) => id,
+            None => return self.notification(method, params),
+        };
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1, // The client sends the whole text on changes.
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "lox-lsp", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/completion" => self.completion(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.document_symbol(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/references" => self.references(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'.", method))),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        vec![response]
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None => return Vec::new(),
+        };
+        let text = match method {
+            "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+            // Only the last change matters since each one is the whole text.
+            "textDocument/didChange" => params["contentChanges"]
+                .as_array()
+                .and_then(|changes| changes.last())
+                .and_then(|change| change["text"].as_str()),
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => None,
+        };
+
+        match text {
+            Some(text) => {
+                let document = Document::new(text.to_string());
+                let diagnostics = document
+                    .analysis
+                    .diagnostics
+                    .iter()
+                    .map(|diagnostic| lsp_diagnostic(&uri, &document, diagnostic))
+                    .collect();
+                self.documents.insert(uri.clone(), document);
+                vec![publish_diagnostics(&uri, diagnostics)]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Finds the document and offset a request is about.
+    fn position<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Result<(&'a str, &'a Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'.", uri)))?;
+        let offset = document
+            .offset(&params["position"])
+            .ok_or_else(|| (INVALID_PARAMS, "Invalid position.".to_string()))?;
+        Ok((uri, document, offset))
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (_, document, offset) = self.position(params)?;
+        let items: Vec<Value> = document
+            .analysis
+            .completions(offset)
+            .into_iter()
+            .map(|completion| {
+                json!({
+                    "label": completion.label,
+                    "kind": completion.kind.completion_kind(),
+                    "detail": format!("{} {}", completion.kind, completion.detail),
+                })
+            })
+            .collect();
+        Ok(json!(items))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, document, offset) = self.position(params)?;
+        let locations: Vec<Value> = document
+            .analysis
+            .definitions(offset)
+            .into_iter()
+            .map(|symbol| json!({ "uri": uri, "range": document.range(symbol.span) }))
+            .collect();
+        Ok(json!(locations))
+    }
+
+    fn document_symbol(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'.", uri)))?;
+
+        let symbols: Vec<Value> = document
+            .analysis
+            .outline()
+            .into_iter()
+            .map(|symbol| document_symbol(document, symbol))
+            .collect();
+        Ok(json!(symbols))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (_, document, offset) = self.position(params)?;
+        Ok(match document.analysis.hover(offset) {
+            Some(markdown) => json!({ "contents": { "kind": "markdown", "value": markdown } }),
+            None => Value::Null,
+        })
+    }
+
+    fn references(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, document, offset) = self.position(params)?;
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+        let locations: Vec<Value> = document
+            .analysis
+            .references(offset, include_declaration)
+            .into_iter()
+            .map(|span| json!({ "uri": uri, "range": document.range(span) }))
+            .collect();
+        Ok(json!(locations))
+    }
+}
+
+/// A symbol of the outline with the symbols nested in it.
+fn document_symbol(document: &Document, symbol: usize) -> Value {
+    let analysis = &document.analysis;
+    let children: Vec<Value> = analysis
+        .children(symbol)
+        .into_iter()
+        .map(|child| document_symbol(document, child))
+        .collect();
+    let symbol = &analysis.symbols[symbol];
+    json!({
+        "name": symbol.name,
+        "detail": symbol.detail,
+        "kind": symbol.kind.symbol_kind(),
+        "range": document.range(symbol.extent),
+        "selectionRange": document.range(symbol.span),
+        "children": children,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Errors without a location are shown at the start of the document.
+fn lsp_diagnostic(uri: &str, document: &Document, diagnostic: &Diagnostic) -> Value {
+    let range = document.range(
+        diagnostic
+            .primary
+            .as_ref()
+            .map(|label| label.span)
+            .unwrap_or_default(),
+    );
+    let related: Vec<Value> = diagnostic
+        .secondary
+        .iter()
+        .map(|label| {
+            json!({
+                "location": { "uri": uri, "range": document.range(label.span) },
+                "message": label.message,
+            })
+        })
+        .collect();
+    json!({
+        "range": range,
+        "severity": match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        },
+        "source": "lox",
+        "message": diagnostic.message,
+        "relatedInformation": related,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(messages: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        input
+    }
+
+    fn replies(mut output: &[u8]) -> Vec<Value> {
+        let mut replies = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            replies.push(message);
+        }
+        replies
+    }
+
+    #[test]
+    fn session_over_stdio() {
+        let uri = "file:///test.lox";
+        let request = |id: i64, method: &str, line: i64, character: i64| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": {
+                    "textDocument": { "uri": uri },
+                    "position": { "line": line, "character": character },
+                },
+            })
+        };
+        let input = frame(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": uri, "text": "var a = 1;\nprint a + ;" } },
+            }),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": uri },
+                    "contentChanges": [{ "text": "var a = 1;\nprint a;" }],
+                },
+            }),
+            request(2, "textDocument/definition", 1, 6),
+            request(3, "textDocument/hover", 1, 6),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+
+        let mut output = Vec::new();
+        assert!(Server::new().run(input.as_slice(), &mut output).unwrap());
+        let replies = replies(&output);
+
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], true);
+        let diagnostics = &replies[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["message"], "Expect expression.");
+        assert_eq!(
+            diagnostics[0]["range"]["start"],
+            json!({ "line": 1, "character": 10 })
+        );
+        assert_eq!(replies[2]["params"]["diagnostics"], json!([]));
+        assert_eq!(
+            replies[3]["result"],
+            json!([{
+                "uri": uri,
+                "range": {
+                    "start": { "line": 0, "character": 4 },
+                    "end": { "line": 0, "character": 5 },
+                },
+            }])
+        );
+        assert_eq!(
+            replies[4]["result"]["contents"]["value"],
+            "```lox\nvariable a\n```"
+        );
+        assert_eq!(replies[5]["result"], Value::Null);
+    }
+
+    #[test]
+    fn unknown_requests_fail() {
+        let input = frame(&[json!({ "jsonrpc": "2.0", "id": 1, "method": "bogus" })]);
+        let mut output = Vec::new();
+
+        // The input ends without `exit`.
+        assert!(!Server::new().run(input.as_slice(), &mut output).unwrap());
+        assert_eq!(replies(&output)[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}